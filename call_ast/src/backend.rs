@@ -0,0 +1,139 @@
+use std::fmt;
+
+use crate::Expr;
+
+// A parser producing the shared AST, so callers can switch implementations.
+pub trait Backend {
+  fn name(&self) -> &str;
+
+  fn parse(&self, code: &str) -> Result<Expr, ParseError>;
+}
+
+// `offset` is the byte offset into the input where parsing failed, the
+// message is backend specific and not compared.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+  pub message: String,
+  pub offset: usize,
+}
+
+impl ParseError {
+  pub fn new(message: impl Into<String>, offset: usize) -> ParseError {
+    ParseError {
+      message: message.into(),
+      offset,
+    }
+  }
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} at offset {}", self.message, self.offset)
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+// An input on which the backends disagree, with every backend's result.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+  pub code: String,
+  pub results: Vec<(String, Result<Expr, ParseError>)>,
+}
+
+impl fmt::Display for Mismatch {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "backends disagree on {:?}", self.code)?;
+    for (name, result) in &self.results {
+      match result {
+        Ok(expr) => writeln!(f, "  {name}: {expr}")?,
+        Err(err) => writeln!(f, "  {name}: {err}")?,
+      }
+    }
+    Ok(())
+  }
+}
+
+// Two results agree when both produce the same tree, or both fail at the
+// same offset.
+pub fn agree(a: &Result<Expr, ParseError>, b: &Result<Expr, ParseError>) -> bool {
+  match (a, b) {
+    (Ok(a), Ok(b)) => a == b,
+    (Err(a), Err(b)) => a.offset == b.offset,
+    _ => false,
+  }
+}
+
+// Feeds every input of `corpus` to every backend and returns the inputs on
+// which they do not all agree.
+pub fn differential(backends: &[&dyn Backend], corpus: &[&str]) -> Vec<Mismatch> {
+  corpus
+    .iter()
+    .filter_map(|code| {
+      let results: Vec<_> = backends
+        .iter()
+        .map(|backend| (backend.name().to_owned(), backend.parse(code)))
+        .collect();
+      let same = results.windows(2).all(|pair| agree(&pair[0].1, &pair[1].1));
+      (!same).then(|| Mismatch {
+        code: code.to_string(),
+        results,
+      })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  // integers only, failing at the first other character
+  struct Digits;
+
+  impl Backend for Digits {
+    fn name(&self) -> &str {
+      "digits"
+    }
+
+    fn parse(&self, code: &str) -> Result<Expr, ParseError> {
+      match code.find(|c: char| !c.is_ascii_digit()) {
+        Some(offset) => Err(ParseError::new("expected digit", offset)),
+        None => code
+          .parse()
+          .map(Expr::Num)
+          .map_err(|_| ParseError::new("expected digit", 0)),
+      }
+    }
+  }
+
+  // same grammar, but reports errors at the start of the input
+  struct Sloppy;
+
+  impl Backend for Sloppy {
+    fn name(&self) -> &str {
+      "sloppy"
+    }
+
+    fn parse(&self, code: &str) -> Result<Expr, ParseError> {
+      Digits
+        .parse(code)
+        .map_err(|err| ParseError::new(err.message, 0))
+    }
+  }
+
+  #[test]
+  fn test_differential() {
+    let corpus = ["12", "", "x", "1x"];
+    assert_eq!(differential(&[&Digits, &Digits], &corpus), []);
+
+    let mismatches = differential(&[&Digits, &Sloppy], &corpus);
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].code, "1x");
+    assert_eq!(
+      mismatches[0].to_string(),
+      "backends disagree on \"1x\"\n  \
+       digits: expected digit at offset 1\n  \
+       sloppy: expected digit at offset 0\n"
+    );
+  }
+}
@@ -0,0 +1,254 @@
+// The call-expression AST shared by the parser backends, `parser_combinator`
+// and `parser_combinator_nom` both produce these types.
+
+pub mod backend;
+pub mod printer;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+pub mod visit;
+
+pub use backend::{Backend, ParseError};
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Call {
+  target: String,
+  args: Vec<Expr>,
+}
+
+impl Call {
+  pub fn new(target: impl Into<String>, args: Vec<Expr>) -> Call {
+    Call {
+      target: target.into(),
+      args,
+    }
+  }
+
+  // the name of the called function
+  pub fn target(&self) -> &str {
+    &self.target
+  }
+
+  pub fn args(&self) -> &[Expr] {
+    &self.args
+  }
+
+  pub fn set_target(&mut self, target: impl Into<String>) {
+    self.target = target.into();
+  }
+
+  pub fn args_mut(&mut self) -> &mut Vec<Expr> {
+    &mut self.args
+  }
+
+  // appends an argument, `Call::new("Add", vec![]).arg(1).arg(2)`
+  pub fn arg(mut self, arg: impl Into<Expr>) -> Call {
+    self.args.push(arg.into());
+    self
+  }
+
+  pub fn into_parts(self) -> (String, Vec<Expr>) {
+    (self.target, self.args)
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expr {
+  Num(i64),
+  Float(f64),
+  Str(String),
+  Bool(bool),
+  Null,
+  Call(Call),
+  Binary(BinOp, Box<Expr>, Box<Expr>),
+  Unary(UnaryOp, Box<Expr>),
+  // placeholder for an argument that failed to parse in recovery mode
+  Error,
+}
+
+impl Expr {
+  pub fn call(target: impl Into<String>, args: Vec<Expr>) -> Expr {
+    Expr::Call(Call::new(target, args))
+  }
+
+  pub fn binary(op: BinOp, lhs: impl Into<Expr>, rhs: impl Into<Expr>) -> Expr {
+    Expr::Binary(op, Box::new(lhs.into()), Box::new(rhs.into()))
+  }
+
+  pub fn unary(op: UnaryOp, val: impl Into<Expr>) -> Expr {
+    Expr::Unary(op, Box::new(val.into()))
+  }
+
+  pub fn as_call(&self) -> Option<&Call> {
+    match self {
+      Expr::Call(call) => Some(call),
+      _ => None,
+    }
+  }
+
+  // true for the literals, which have no sub-expressions
+  pub fn is_literal(&self) -> bool {
+    matches!(
+      self,
+      Expr::Num(_) | Expr::Float(_) | Expr::Str(_) | Expr::Bool(_) | Expr::Null
+    )
+  }
+
+  // the direct sub-expressions, arguments in order and operands left to right
+  pub fn children(&self) -> Vec<&Expr> {
+    match self {
+      Expr::Call(call) => call.args.iter().collect(),
+      Expr::Binary(_, lhs, rhs) => vec![lhs, rhs],
+      Expr::Unary(_, val) => vec![val],
+      _ => vec![],
+    }
+  }
+}
+
+impl From<i64> for Expr {
+  fn from(val: i64) -> Expr {
+    Expr::Num(val)
+  }
+}
+
+impl From<f64> for Expr {
+  fn from(val: f64) -> Expr {
+    Expr::Float(val)
+  }
+}
+
+impl From<bool> for Expr {
+  fn from(val: bool) -> Expr {
+    Expr::Bool(val)
+  }
+}
+
+impl From<&str> for Expr {
+  fn from(val: &str) -> Expr {
+    Expr::Str(val.to_owned())
+  }
+}
+
+impl From<String> for Expr {
+  fn from(val: String) -> Expr {
+    Expr::Str(val)
+  }
+}
+
+impl From<Call> for Expr {
+  fn from(val: Call) -> Expr {
+    Expr::Call(val)
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinOp {
+  Or,
+  And,
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Rem,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnaryOp {
+  Neg,
+  Not,
+}
+
+impl BinOp {
+  pub fn token(self) -> &'static str {
+    BINARY_OPERATORS
+      .iter()
+      .find(|(_, _, op)| *op == self)
+      .unwrap()
+      .0
+  }
+
+  pub fn precedence(self) -> u32 {
+    BINARY_OPERATORS
+      .iter()
+      .find(|(_, _, op)| *op == self)
+      .unwrap()
+      .1
+  }
+}
+
+impl UnaryOp {
+  pub fn token(self) -> &'static str {
+    UNARY_OPERATORS
+      .iter()
+      .find(|(_, _, op)| *op == self)
+      .unwrap()
+      .0
+  }
+
+  pub fn precedence(self) -> u32 {
+    UNARY_OPERATORS
+      .iter()
+      .find(|(_, _, op)| *op == self)
+      .unwrap()
+      .1
+  }
+}
+
+// every binary operator is left associative, a higher precedence binds tighter
+// and a longer token goes before its prefixes (`<=` before `<`)
+pub const BINARY_OPERATORS: [(&str, u32, BinOp); 13] = [
+  ("||", 1, BinOp::Or),
+  ("&&", 2, BinOp::And),
+  ("==", 3, BinOp::Eq),
+  ("!=", 3, BinOp::Ne),
+  ("<=", 4, BinOp::Le),
+  (">=", 4, BinOp::Ge),
+  ("<", 4, BinOp::Lt),
+  (">", 4, BinOp::Gt),
+  ("+", 5, BinOp::Add),
+  ("-", 5, BinOp::Sub),
+  ("*", 6, BinOp::Mul),
+  ("/", 6, BinOp::Div),
+  ("%", 6, BinOp::Rem),
+];
+
+pub const UNARY_OPERATORS: [(&str, u32, UnaryOp); 2] =
+  [("-", 7, UnaryOp::Neg), ("!", 7, UnaryOp::Not)];
+
+// What a backend skips between tokens, each one has its own `trivia` parser
+// taking this
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trivia {
+  // spaces, tabs, newlines and any other Unicode whitespace
+  pub whitespace: bool,
+  // `// ...` up to the end of the line
+  pub line_comments: bool,
+  // `/* ... */`, not nested
+  pub block_comments: bool,
+}
+
+impl Trivia {
+  pub const NONE: Trivia = Trivia {
+    whitespace: false,
+    line_comments: false,
+    block_comments: false,
+  };
+
+  pub const ALL: Trivia = Trivia {
+    whitespace: true,
+    line_comments: true,
+    block_comments: true,
+  };
+}
+
+// spaces, newlines and comments are allowed between any two tokens
+pub const TRIVIA: Trivia = Trivia::ALL;
@@ -0,0 +1,248 @@
+use std::fmt;
+
+use crate::{Call, Expr};
+
+// operands bind tighter than any operator
+const ATOM: u32 = u32::MAX;
+
+// Canonical source text for an `Expr`: one space around binary operators,
+// parenthesis only where the precedences need them, so `parse` gives the same
+// tree back. `Expr::Error` and non-finite floats have no source form.
+impl fmt::Display for Expr {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Expr::Num(n) => write!(f, "{n}"),
+      Expr::Float(n) => write!(f, "{n:?}"),
+      Expr::Str(s) => write_str(f, s),
+      Expr::Bool(b) => write!(f, "{b}"),
+      Expr::Null => write!(f, "null"),
+      Expr::Call(call) => write!(f, "{call}"),
+      Expr::Binary(op, lhs, rhs) => {
+        write_operand(f, lhs, op.precedence(), false)?;
+        write!(f, " {} ", op.token())?;
+        write_operand(f, rhs, op.precedence() + 1, false)
+      }
+      Expr::Unary(op, val) => {
+        write!(f, "{}", op.token())?;
+        write_operand(f, val, op.precedence(), true)
+      }
+      Expr::Error => write!(f, "<error>"),
+    }
+  }
+}
+
+impl fmt::Display for Call {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}(", self.target())?;
+    for (i, arg) in self.args().iter().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      write!(f, "{arg}")?;
+    }
+    write!(f, ")")
+  }
+}
+
+fn write_operand(
+  f: &mut fmt::Formatter<'_>,
+  expr: &Expr,
+  min_precedence: u32,
+  after_prefix: bool,
+) -> fmt::Result {
+  if needs_parens(expr, min_precedence, after_prefix) {
+    write!(f, "({expr})")
+  } else {
+    write!(f, "{expr}")
+  }
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+  write!(f, "\"")?;
+  for c in s.chars() {
+    match c {
+      '\n' => write!(f, "\\n")?,
+      '\r' => write!(f, "\\r")?,
+      '\t' => write!(f, "\\t")?,
+      '\0' => write!(f, "\\0")?,
+      '\\' => write!(f, "\\\\")?,
+      '"' => write!(f, "\\\"")?,
+      c => write!(f, "{c}")?,
+    }
+  }
+  write!(f, "\"")
+}
+
+fn precedence(expr: &Expr) -> u32 {
+  match expr {
+    Expr::Binary(op, _, _) => op.precedence(),
+    Expr::Unary(op, _) => op.precedence(),
+    _ => ATOM,
+  }
+}
+
+// a numeric literal right after a prefix operator would take it as its sign,
+// `-(1)` is not `-1`
+fn needs_parens(expr: &Expr, min_precedence: u32, after_prefix: bool) -> bool {
+  let literal = matches!(expr, Expr::Num(_) | Expr::Float(_));
+  precedence(expr) < min_precedence || (after_prefix && literal)
+}
+
+// Like `Display` but argument lists that do not fit in `width` columns are
+// broken one argument per line, indented by two spaces:
+//
+// Foo(
+//   Bar(1, 2),
+//   "a long string"
+// )
+pub fn pretty(expr: &Expr, width: usize) -> String {
+  let mut printer = Printer {
+    out: String::new(),
+    width,
+    indent: 0,
+  };
+  printer.expr(expr);
+  printer.out
+}
+
+struct Printer {
+  out: String,
+  width: usize,
+  indent: usize,
+}
+
+impl Printer {
+  fn column(&self) -> usize {
+    let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+    self.out[line_start..].chars().count()
+  }
+
+  fn expr(&mut self, expr: &Expr) {
+    let flat = expr.to_string();
+    if self.column() + flat.chars().count() <= self.width {
+      self.out.push_str(&flat);
+      return;
+    }
+
+    match expr {
+      Expr::Call(call) if !call.args().is_empty() => {
+        self.out.push_str(call.target());
+        self.out.push('(');
+        self.indent += 2;
+        for (i, arg) in call.args().iter().enumerate() {
+          if i > 0 {
+            self.out.push(',');
+          }
+          self.newline();
+          self.expr(arg);
+        }
+        self.indent -= 2;
+        self.newline();
+        self.out.push(')');
+      }
+      Expr::Binary(op, lhs, rhs) => {
+        self.operand(lhs, op.precedence(), false);
+        self.out.push_str(&format!(" {} ", op.token()));
+        self.operand(rhs, op.precedence() + 1, false);
+      }
+      Expr::Unary(op, val) => {
+        self.out.push_str(op.token());
+        self.operand(val, op.precedence(), true);
+      }
+      _ => self.out.push_str(&flat),
+    }
+  }
+
+  fn operand(&mut self, expr: &Expr, min_precedence: u32, after_prefix: bool) {
+    if needs_parens(expr, min_precedence, after_prefix) {
+      self.out.push('(');
+      self.expr(expr);
+      self.out.push(')');
+    } else {
+      self.expr(expr);
+    }
+  }
+
+  fn newline(&mut self) {
+    self.out.push('\n');
+    self.out.push_str(&" ".repeat(self.indent));
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::{BinOp, UnaryOp};
+
+  fn call(target: &str, args: Vec<Expr>) -> Expr {
+    Expr::Call(Call::new(target, args))
+  }
+
+  fn binary(op: BinOp, lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+  }
+
+  #[test]
+  fn test_display() {
+    assert_eq!(Expr::Num(-1).to_string(), "-1");
+    assert_eq!(Expr::Float(1.0).to_string(), "1.0");
+    assert_eq!(Expr::Float(2e20).to_string(), "2e20");
+    assert_eq!(
+      Expr::Str("a\"b\\c\nd ñ".to_owned()).to_string(),
+      r#""a\"b\\c\nd ñ""#
+    );
+    assert_eq!(Expr::Null.to_string(), "null");
+    assert_eq!(
+      call("Foo", vec![Expr::Bool(true), call("Bar", vec![])]).to_string(),
+      "Foo(true, Bar())"
+    );
+
+    let sum = binary(BinOp::Add, Expr::Num(1), Expr::Num(2));
+    assert_eq!(
+      binary(BinOp::Mul, sum.clone(), Expr::Num(3)).to_string(),
+      "(1 + 2) * 3"
+    );
+    assert_eq!(
+      binary(BinOp::Sub, Expr::Num(1), sum.clone()).to_string(),
+      "1 - (1 + 2)"
+    );
+    assert_eq!(
+      binary(BinOp::Add, sum.clone(), Expr::Num(3)).to_string(),
+      "1 + 2 + 3"
+    );
+    assert_eq!(
+      Expr::Unary(UnaryOp::Neg, Box::new(Expr::Num(1))).to_string(),
+      "-(1)"
+    );
+    assert_eq!(
+      Expr::Unary(UnaryOp::Not, Box::new(sum)).to_string(),
+      "!(1 + 2)"
+    );
+  }
+
+  #[test]
+  fn test_pretty() {
+    let expr = call(
+      "Foo",
+      vec![
+        call("Bar", vec![Expr::Num(1), Expr::Num(2)]),
+        Expr::Str("a long string".to_owned()),
+      ],
+    );
+    assert_eq!(pretty(&expr, 80), r#"Foo(Bar(1, 2), "a long string")"#);
+    assert_eq!(
+      pretty(&expr, 20),
+      "Foo(\n  Bar(1, 2),\n  \"a long string\"\n)"
+    );
+    assert_eq!(
+      pretty(&expr, 8),
+      "Foo(\n  Bar(\n    1,\n    2\n  ),\n  \"a long string\"\n)"
+    );
+
+    let expr = binary(BinOp::Mul, expr, Expr::Num(2));
+    assert_eq!(
+      pretty(&expr, 20),
+      "Foo(\n  Bar(1, 2),\n  \"a long string\"\n) * 2"
+    );
+  }
+}
@@ -0,0 +1,308 @@
+// proptest strategies generating ASTs and source text, for round-trip and
+// differential tests
+
+use proptest::prelude::*;
+
+use crate::{BinOp, Expr, UnaryOp};
+
+// identifiers that are not one of the keywords, mostly ASCII
+pub fn ident() -> impl Strategy<Value = String> {
+  "[a-zA-Z_ñλ名][a-zA-Z0-9_ñλ名\u{301}]{0,8}".prop_filter("keywords are not identifiers", |s| {
+    !["true", "false", "null"].contains(&s.as_str())
+  })
+}
+
+// any tree a parser can produce: finite floats and no `Expr::Error`
+pub fn arb_expr() -> impl Strategy<Value = Expr> {
+  let leaf = prop_oneof![
+    any::<i64>().prop_map(Expr::Num),
+    (proptest::num::f64::NORMAL | proptest::num::f64::ZERO).prop_map(Expr::Float),
+    any::<String>().prop_map(Expr::Str),
+    any::<bool>().prop_map(Expr::Bool),
+    Just(Expr::Null),
+  ];
+  let binary_ops = vec![
+    BinOp::Or,
+    BinOp::And,
+    BinOp::Eq,
+    BinOp::Ne,
+    BinOp::Lt,
+    BinOp::Le,
+    BinOp::Gt,
+    BinOp::Ge,
+    BinOp::Add,
+    BinOp::Sub,
+    BinOp::Mul,
+    BinOp::Div,
+    BinOp::Rem,
+  ];
+  leaf.prop_recursive(4, 32, 4, move |inner| {
+    prop_oneof![
+      (ident(), prop::collection::vec(inner.clone(), 0..4))
+        .prop_map(|(target, args)| Expr::call(target, args)),
+      (
+        prop::sample::select(binary_ops.clone()),
+        inner.clone(),
+        inner.clone()
+      )
+        .prop_map(|(op, lhs, rhs)| Expr::binary(op, lhs, rhs)),
+      (
+        prop::sample::select(vec![UnaryOp::Neg, UnaryOp::Not]),
+        inner
+      )
+        .prop_map(|(op, val)| Expr::unary(op, val)),
+    ]
+  })
+}
+
+// operands bind tighter than any operator
+const ATOM: u32 = u32::MAX;
+
+// Source text together with the tree it must parse to, and the precedence of
+// its outermost operator to know when it needs parenthesis.
+#[derive(Debug, Clone)]
+struct Fragment {
+  text: String,
+  expr: Expr,
+  precedence: u32,
+}
+
+impl Fragment {
+  fn atom(text: String, expr: Expr) -> Fragment {
+    Fragment {
+      text,
+      expr,
+      precedence: ATOM,
+    }
+  }
+
+  fn wrapped(&self, wrap: bool) -> String {
+    if wrap {
+      format!("({})", self.text)
+    } else {
+      self.text.clone()
+    }
+  }
+}
+
+// anything that may go between two tokens
+fn trivia() -> impl Strategy<Value = String> {
+  let piece = prop_oneof![
+    Just(" "),
+    Just("\n"),
+    Just("\t"),
+    Just("\r\n"),
+    Just("\u{a0}"),
+    Just("// note\n"),
+    Just("/* note */"),
+    Just("/**/"),
+  ];
+  prop::collection::vec(piece, 0..3).prop_map(|pieces| pieces.concat())
+}
+
+fn int_literal() -> impl Strategy<Value = Fragment> {
+  (any::<i64>(), any::<bool>()).prop_map(|(n, plus)| {
+    let text = if plus && n >= 0 {
+      format!("+{n}")
+    } else {
+      n.to_string()
+    };
+    Fragment::atom(text, Expr::Num(n))
+  })
+}
+
+fn float_literal() -> impl Strategy<Value = Fragment> {
+  let val = proptest::num::f64::NORMAL | proptest::num::f64::ZERO;
+  (val, 0..3u8).prop_map(|(n, style)| {
+    let text = match style {
+      0 => format!("{n:?}"),
+      1 => format!("{n:e}"),
+      _ => format!("{n:E}"),
+    };
+    Fragment::atom(text, Expr::Float(n))
+  })
+}
+
+// every character either as is or escaped
+fn string_literal() -> impl Strategy<Value = Fragment> {
+  prop::collection::vec((any::<char>(), any::<bool>()), 0..8).prop_map(|chars| {
+    let mut text = String::from("\"");
+    for &(c, escape) in &chars {
+      match c {
+        '"' => text.push_str("\\\""),
+        '\\' => text.push_str("\\\\"),
+        '\n' if escape => text.push_str("\\n"),
+        '\r' if escape => text.push_str("\\r"),
+        '\t' if escape => text.push_str("\\t"),
+        '\0' if escape => text.push_str("\\0"),
+        c if escape => text.push_str(&format!("\\u{{{:x}}}", c as u32)),
+        c => text.push(c),
+      }
+    }
+    text.push('"');
+    let val = chars.into_iter().map(|(c, _)| c).collect();
+    Fragment::atom(text, Expr::Str(val))
+  })
+}
+
+fn fragment() -> impl Strategy<Value = Fragment> {
+  let leaf = prop_oneof![
+    int_literal(),
+    float_literal(),
+    string_literal(),
+    any::<bool>().prop_map(|b| Fragment::atom(b.to_string(), Expr::Bool(b))),
+    Just(Fragment::atom("null".to_owned(), Expr::Null)),
+  ];
+  let binary_ops: Vec<_> = crate::BINARY_OPERATORS
+    .iter()
+    .map(|(_, _, op)| *op)
+    .collect();
+  // enough trivia for the tokens of any node
+  let pad = || prop::collection::vec(trivia(), 4);
+  leaf.prop_recursive(4, 32, 4, move |inner| {
+    prop_oneof![
+      (ident(), prop::collection::vec(inner.clone(), 0..4), pad()).prop_map(
+        |(target, args, pad)| {
+          let text = args
+            .iter()
+            .map(|arg| format!("{}{}{}", pad[1], arg.text, pad[2]))
+            .collect::<Vec<_>>()
+            .join(",");
+          let text = format!("{target}{}({text}{})", pad[0], pad[3]);
+          let args = args.into_iter().map(|arg| arg.expr).collect();
+          Fragment::atom(text, Expr::call(target, args))
+        }
+      ),
+      (
+        prop::sample::select(binary_ops.clone()),
+        inner.clone(),
+        inner.clone(),
+        pad()
+      )
+        .prop_map(|(op, lhs, rhs, mut pad)| {
+          let precedence = op.precedence();
+          // `a //* ... */ b` would start a line comment
+          if op == BinOp::Div && pad[1].starts_with('/') {
+            pad[1].insert(0, ' ');
+          }
+          // left associative
+          let text = format!(
+            "{}{}{}{}{}",
+            lhs.wrapped(lhs.precedence < precedence),
+            pad[0],
+            op.token(),
+            pad[1],
+            rhs.wrapped(rhs.precedence <= precedence)
+          );
+          Fragment {
+            text,
+            expr: Expr::binary(op, lhs.expr, rhs.expr),
+            precedence,
+          }
+        }),
+      (
+        prop::sample::select(vec![UnaryOp::Neg, UnaryOp::Not]),
+        inner.clone(),
+        trivia()
+      )
+        .prop_map(|(op, val, pad)| {
+          let precedence = op.precedence();
+          // a numeric literal would take the operator as its sign
+          let numeric = matches!(val.expr, Expr::Num(_) | Expr::Float(_));
+          let wrap = numeric || val.precedence < precedence;
+          Fragment {
+            text: format!("{}{pad}{}", op.token(), val.wrapped(wrap)),
+            expr: Expr::unary(op, val.expr),
+            precedence,
+          }
+        }),
+      // redundant parenthesis
+      (inner, trivia(), trivia()).prop_map(|(val, before, after)| {
+        Fragment::atom(format!("({before}{}{after})", val.text), val.expr)
+      }),
+    ]
+  })
+}
+
+// valid source text, with varied spelling, trivia and parenthesis, and the
+// tree it parses to
+pub fn arb_source() -> impl Strategy<Value = (String, Expr)> {
+  (trivia(), fragment(), trivia())
+    .prop_map(|(before, val, after)| (format!("{before}{}{after}", val.text), val.expr))
+}
+
+// pieces of the grammar, and a few things around it, spliced into valid
+// source by `arb_mutated`
+const TOKENS: [&str; 28] = [
+  "(",
+  ")",
+  ",",
+  "\"",
+  "\\",
+  "\\u{",
+  "}",
+  "-",
+  "+",
+  "!",
+  "=",
+  "<",
+  "*",
+  "/",
+  "/*",
+  "*/",
+  "//",
+  "\n",
+  " ",
+  "0",
+  "99999999999999999999",
+  ".",
+  "e",
+  "_",
+  "a",
+  "true",
+  "null",
+  "\u{e9}",
+];
+
+#[derive(Debug, Clone)]
+enum Edit {
+  Delete(prop::sample::Index, usize),
+  Insert(prop::sample::Index, &'static str),
+  Replace(prop::sample::Index, &'static str),
+}
+
+fn edit() -> impl Strategy<Value = Edit> {
+  let token = || prop::sample::select(&TOKENS[..]);
+  prop_oneof![
+    (any::<prop::sample::Index>(), 1..4usize).prop_map(|(at, len)| Edit::Delete(at, len)),
+    (any::<prop::sample::Index>(), token()).prop_map(|(at, token)| Edit::Insert(at, token)),
+    (any::<prop::sample::Index>(), token()).prop_map(|(at, token)| Edit::Replace(at, token)),
+  ]
+}
+
+// valid source with a few characters deleted, inserted or replaced, mostly
+// invalid but close to the grammar
+pub fn arb_mutated() -> impl Strategy<Value = String> {
+  (arb_source(), prop::collection::vec(edit(), 1..4)).prop_map(|((text, _), edits)| {
+    let mut chars: Vec<char> = text.chars().collect();
+    for edit in edits {
+      match edit {
+        Edit::Delete(at, len) if !chars.is_empty() => {
+          let start = at.index(chars.len());
+          let end = (start + len).min(chars.len());
+          chars.drain(start..end);
+        }
+        Edit::Insert(at, token) => {
+          let at = at.index(chars.len() + 1);
+          chars.splice(at..at, token.chars());
+        }
+        Edit::Replace(at, token) if !chars.is_empty() => {
+          let at = at.index(chars.len());
+          chars.splice(at..at + 1, token.chars());
+        }
+        _ => {}
+      }
+    }
+    chars.into_iter().collect()
+  })
+}
@@ -0,0 +1,270 @@
+use crate::{BinOp, Call, Expr, UnaryOp};
+
+// Read-only traversal of an `Expr` tree. Every method defaults to visiting
+// the children, override the ones you care about and call the matching
+// `walk_*` function to keep going down.
+pub trait Visitor {
+  fn visit_expr(&mut self, expr: &Expr) {
+    walk_expr(self, expr);
+  }
+
+  fn visit_call(&mut self, call: &Call) {
+    walk_call(self, call);
+  }
+
+  fn visit_binary(&mut self, _op: BinOp, lhs: &Expr, rhs: &Expr) {
+    self.visit_expr(lhs);
+    self.visit_expr(rhs);
+  }
+
+  fn visit_unary(&mut self, _op: UnaryOp, val: &Expr) {
+    self.visit_expr(val);
+  }
+
+  // numbers, strings, booleans, null and `Expr::Error`
+  fn visit_leaf(&mut self, _expr: &Expr) {}
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+  match expr {
+    Expr::Call(call) => visitor.visit_call(call),
+    Expr::Binary(op, lhs, rhs) => visitor.visit_binary(*op, lhs, rhs),
+    Expr::Unary(op, val) => visitor.visit_unary(*op, val),
+    _ => visitor.visit_leaf(expr),
+  }
+}
+
+pub fn walk_call<V: Visitor + ?Sized>(visitor: &mut V, call: &Call) {
+  for arg in call.args() {
+    visitor.visit_expr(arg);
+  }
+}
+
+// Like `Visitor` but the tree can be changed in place
+pub trait VisitorMut {
+  fn visit_expr_mut(&mut self, expr: &mut Expr) {
+    walk_expr_mut(self, expr);
+  }
+
+  fn visit_call_mut(&mut self, call: &mut Call) {
+    walk_call_mut(self, call);
+  }
+
+  fn visit_binary_mut(&mut self, _op: &mut BinOp, lhs: &mut Expr, rhs: &mut Expr) {
+    self.visit_expr_mut(lhs);
+    self.visit_expr_mut(rhs);
+  }
+
+  fn visit_unary_mut(&mut self, _op: &mut UnaryOp, val: &mut Expr) {
+    self.visit_expr_mut(val);
+  }
+
+  fn visit_leaf_mut(&mut self, _expr: &mut Expr) {}
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+  match expr {
+    Expr::Call(call) => visitor.visit_call_mut(call),
+    Expr::Binary(op, lhs, rhs) => visitor.visit_binary_mut(op, lhs, rhs),
+    Expr::Unary(op, val) => visitor.visit_unary_mut(op, val),
+    _ => visitor.visit_leaf_mut(expr),
+  }
+}
+
+pub fn walk_call_mut<V: VisitorMut + ?Sized>(visitor: &mut V, call: &mut Call) {
+  for arg in call.args_mut() {
+    visitor.visit_expr_mut(arg);
+  }
+}
+
+// Rebuilds a tree by value. `fold_expr` defaults to folding the children
+// first, override it to rewrite nodes after (or instead of) that.
+pub trait Fold {
+  fn fold_expr(&mut self, expr: Expr) -> Expr {
+    fold_children(self, expr)
+  }
+
+  fn fold_call(&mut self, call: Call) -> Call {
+    let (target, args) = call.into_parts();
+    let args = args.into_iter().map(|arg| self.fold_expr(arg)).collect();
+    Call::new(target, args)
+  }
+}
+
+// folds the direct sub-expressions of `expr` and keeps the node itself
+pub fn fold_children<F: Fold + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+  match expr {
+    Expr::Call(call) => Expr::Call(folder.fold_call(call)),
+    Expr::Binary(op, lhs, rhs) => Expr::binary(op, folder.fold_expr(*lhs), folder.fold_expr(*rhs)),
+    Expr::Unary(op, val) => Expr::unary(op, folder.fold_expr(*val)),
+    leaf => leaf,
+  }
+}
+
+// rewrites every node bottom-up with `op`, children before their parent
+pub fn fold_expr(expr: Expr, op: impl FnMut(Expr) -> Expr) -> Expr {
+  struct Bottom<F>(F);
+
+  impl<F: FnMut(Expr) -> Expr> Fold for Bottom<F> {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+      let expr = fold_children(self, expr);
+      (self.0)(expr)
+    }
+  }
+
+  Bottom(op).fold_expr(expr)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_visitor() {
+    // a lint pass collecting calls that are not in a known list
+    struct Unknown<'a> {
+      known: &'a [&'a str],
+      found: Vec<String>,
+    }
+
+    impl Visitor for Unknown<'_> {
+      fn visit_call(&mut self, call: &Call) {
+        if !self.known.contains(&call.target()) {
+          self.found.push(call.target().to_owned());
+        }
+        walk_call(self, call);
+      }
+    }
+
+    // Add(Foo(1), 2 * Bar(Add(3, 4)), -Baz())
+    let expr = Expr::call(
+      "Add",
+      vec![
+        Expr::call("Foo", vec![Expr::Num(1)]),
+        Expr::binary(
+          BinOp::Mul,
+          2,
+          Expr::call("Bar", vec![Expr::call("Add", vec![3.into(), 4.into()])]),
+        ),
+        Expr::unary(UnaryOp::Neg, Expr::call("Baz", vec![])),
+      ],
+    );
+    let mut lint = Unknown {
+      known: &["Add"],
+      found: vec![],
+    };
+    lint.visit_expr(&expr);
+    assert_eq!(lint.found, ["Foo", "Bar", "Baz"]);
+  }
+
+  #[test]
+  fn test_visitor_mut() {
+    struct Rename;
+
+    impl VisitorMut for Rename {
+      fn visit_call_mut(&mut self, call: &mut Call) {
+        call.set_target(call.target().to_lowercase());
+        walk_call_mut(self, call);
+      }
+
+      fn visit_leaf_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Num(n) = expr {
+          *n *= 10;
+        }
+      }
+    }
+
+    let tree = |foo, bar, scale| {
+      Expr::call(
+        foo,
+        vec![
+          Expr::Num(scale),
+          Expr::binary(
+            BinOp::Add,
+            Expr::call(bar, vec![Expr::Num(2 * scale)]),
+            3 * scale,
+          ),
+        ],
+      )
+    };
+    let mut expr = tree("Foo", "Bar", 1);
+    Rename.visit_expr_mut(&mut expr);
+    assert_eq!(expr, tree("foo", "bar", 10));
+  }
+
+  #[test]
+  fn test_fold() {
+    // constant folding of integer additions
+    // Foo(1 + 2 + 3, 4 + Bar())
+    let expr = Expr::call(
+      "Foo",
+      vec![
+        Expr::binary(BinOp::Add, Expr::binary(BinOp::Add, 1, 2), 3),
+        Expr::binary(BinOp::Add, 4, Expr::call("Bar", vec![])),
+      ],
+    );
+    let folded = fold_expr(expr, |expr| match expr {
+      Expr::Binary(BinOp::Add, lhs, rhs) => match (*lhs, *rhs) {
+        (Expr::Num(a), Expr::Num(b)) => Expr::Num(a + b),
+        (lhs, rhs) => Expr::binary(BinOp::Add, lhs, rhs),
+      },
+      expr => expr,
+    });
+    assert_eq!(
+      folded,
+      Expr::call(
+        "Foo",
+        vec![
+          Expr::Num(6),
+          Expr::binary(BinOp::Add, 4, Expr::call("Bar", vec![]))
+        ]
+      )
+    );
+
+    // `Fold::fold_call` is reached for every call, nested ones included
+    struct Count(usize);
+
+    impl Fold for Count {
+      fn fold_call(&mut self, call: Call) -> Call {
+        self.0 += 1;
+        let (target, args) = call.into_parts();
+        let args = args.into_iter().map(|arg| self.fold_expr(arg)).collect();
+        Call::new(target, args)
+      }
+    }
+
+    let mut count = Count(0);
+    let expr = Expr::call(
+      "A",
+      vec![
+        Expr::call("B", vec![]),
+        Expr::call("C", vec![Expr::call("D", vec![])]),
+      ],
+    );
+    assert_eq!(count.fold_expr(expr.clone()), expr);
+    assert_eq!(count.0, 4);
+  }
+
+  #[test]
+  fn test_builders() {
+    let call = Call::new("Add", vec![]).arg(1).arg(2.5).arg("a").arg(true);
+    assert_eq!(call.target(), "Add");
+    assert_eq!(
+      call.args(),
+      [
+        Expr::Num(1),
+        Expr::Float(2.5),
+        Expr::Str("a".to_owned()),
+        Expr::Bool(true)
+      ]
+    );
+
+    let expr = Expr::from(call);
+    assert_eq!(expr.children().len(), 4);
+    assert!(expr.children().iter().all(|child| child.is_literal()));
+    assert_eq!(expr.as_call().map(Call::target), Some("Add"));
+
+    let expr = Expr::unary(UnaryOp::Not, Expr::binary(BinOp::Lt, 1, 2));
+    assert_eq!(expr.children(), [&Expr::binary(BinOp::Lt, 1, 2)]);
+  }
+}
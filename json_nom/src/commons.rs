@@ -1,8 +1,11 @@
 use nom::{
-  character::complete::{alpha1, alphanumeric0, char},
-  multi::many0,
+  character::complete::{char, satisfy},
+  combinator::recognize,
+  multi::{many0, many0_count},
+  sequence::pair,
   IResult, Parser,
 };
+use unicode_ident::{is_xid_continue, is_xid_start};
 
 pub fn whitespace(input: &str) -> IResult<&str, ()> {
   let ws = char(' ').or(char('\n')).or(char('\r')).or(char('\t'));
@@ -10,10 +13,13 @@ pub fn whitespace(input: &str) -> IResult<&str, ()> {
   Ok((input, ()))
 }
 
+// ident = ( XID_Start | "_" ) XID_Continue *, Unicode identifiers as in Rust
 pub fn ident(input: &str) -> nom::IResult<&str, String> {
-  let (input, (first, second)) = alpha1.and(alphanumeric0).parse(input)?;
-  let val = format!("{}{}", first, second);
-  Ok((input, val))
+  let (input, val) = recognize(pair(
+    satisfy(|c| c == '_' || is_xid_start(c)),
+    many0_count(satisfy(is_xid_continue)),
+  ))(input)?;
+  Ok((input, val.to_owned()))
 }
 
 #[cfg(test)]
@@ -46,5 +52,11 @@ mod test {
     let (input, val) = ident("foo2A3dEz(").unwrap();
     assert_eq!(val, "foo2A3dEz");
     assert_eq!(input, "(");
+
+    let (input, val) = ident("_ñandú名 1").unwrap();
+    assert_eq!(val, "_ñandú名");
+    assert_eq!(input, " 1");
+    assert!(ident("2a").is_err());
+    assert!(ident("\u{301}a").is_err());
   }
 }
@@ -0,0 +1,102 @@
+use nom::error::{Error, ErrorKind};
+use parser_combinator::{diagnostics::Diagnostic, foundation::Ctx, foundation::Span};
+
+use crate::j_value::JValue;
+
+// parses a whole document, reporting errors as renderable diagnostics
+pub fn parse_document(source: &str) -> Result<JValue, Diagnostic> {
+  let (rest, val) = JValue::parse(source).map_err(|err| diagnostic(source, &err))?;
+  if !rest.is_empty() {
+    let span = token_span(source, offset(source, rest));
+    let expected = ["end of input".to_string()];
+    return Err(Diagnostic::unexpected(source, span).with_expected(expected));
+  }
+  Ok(val)
+}
+
+pub fn diagnostic(source: &str, err: &nom::Err<Error<&str>>) -> Diagnostic {
+  let e = match err {
+    nom::Err::Error(e) | nom::Err::Failure(e) => e,
+    nom::Err::Incomplete(_) => {
+      let span = Span::new(source.len(), source.len());
+      return Diagnostic::unexpected(source, span);
+    }
+  };
+
+  let span = token_span(source, offset(source, e.input));
+  Diagnostic::unexpected(source, span).with_expected([expected(e.code)])
+}
+
+// byte offset of `rest` inside `source`, nom errors always point to a suffix of the input
+fn offset(source: &str, rest: &str) -> usize {
+  let start = source.as_ptr() as usize;
+  let ptr = rest.as_ptr() as usize;
+  if (start..=start + source.len()).contains(&ptr) {
+    ptr - start
+  } else {
+    source.len().saturating_sub(rest.len())
+  }
+}
+
+fn token_span(source: &str, offset: usize) -> Span {
+  Ctx::new(source).skip(offset).token_span()
+}
+
+fn expected(code: ErrorKind) -> String {
+  let expected = match code {
+    ErrorKind::Alpha => "letter",
+    ErrorKind::Digit => "digit",
+    ErrorKind::Char => "character",
+    ErrorKind::Tag => "true, false or null",
+    ErrorKind::Escaped => "escape sequence",
+    ErrorKind::IsA => "printable ASCII character",
+    other => return other.description().to_lowercase(),
+  };
+  expected.to_string()
+}
+
+#[cfg(test)]
+mod test {
+  use super::parse_document;
+  use crate::j_value::JValue;
+  use parser_combinator::foundation::Span;
+
+  #[test]
+  fn test_parse_document() {
+    let Ok(JValue::Array(vec)) = parse_document("[1, true]") else {
+      unreachable!()
+    };
+    assert_eq!(vec.len(), 2);
+  }
+
+  #[test]
+  fn test_bad_keyword() {
+    let source = "{\n  \"a\": trueish\n}";
+    let diagnostic = parse_document(source).unwrap_err();
+    assert_eq!(diagnostic.message(), "unexpected `trueish`");
+    assert_eq!(diagnostic.span(), Span::new(9, 16));
+
+    let expected = "\
+error: unexpected `trueish`
+ --> 2:8
+  |
+2 |   \"a\": trueish
+  |        ^^^^^^^ expected true, false or null
+";
+    assert_eq!(diagnostic.render(source, false), expected);
+  }
+
+  #[test]
+  fn test_trailing_input() {
+    let diagnostic = parse_document("[1] 2").unwrap_err();
+    assert_eq!(diagnostic.span(), Span::new(4, 5));
+    assert_eq!(diagnostic.expected(), ["end of input".to_string()]);
+  }
+
+  #[test]
+  fn test_bad_escape() {
+    let diagnostic = parse_document(r#"["\q"]"#).unwrap_err();
+    assert_eq!(diagnostic.span(), Span::new(2, 3));
+    assert_eq!(diagnostic.expected(), ["escape sequence".to_string()]);
+  }
+}
@@ -3,18 +3,14 @@ use nom::Err::Failure;
 
 use crate::{commons::ident, j_value::JValue};
 
-pub fn raw_bool_or_null(input: &str) -> nom::IResult<&str, JValue> {
-  let (input, identifier) = ident(input)?;
+pub fn raw_bool_or_null(raw_input: &str) -> nom::IResult<&str, JValue> {
+  let (input, identifier) = ident(raw_input)?;
   let output = match identifier.as_str() {
     "true" => JValue::Bool(true),
     "false" => JValue::Bool(false),
     "null" => JValue::Null,
-    _ => {
-      return Err(Failure(Error::new(
-        "Expected null, true or false",
-        ErrorKind::Tag,
-      )))
-    }
+    // expected null, true or false
+    _ => return Err(Failure(Error::new(raw_input, ErrorKind::Tag))),
   };
   Ok((input, output))
 }
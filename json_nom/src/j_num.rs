@@ -16,7 +16,7 @@ impl Dec {
     let negative = sign.is_some();
 
     let (input, integer): (&str, Vec<u8>) = char('0')
-      .map(|_| vec![0])
+      .map(|_| vec![b'0'])
       .or(digit1.map(|d: &str| Vec::from(d.as_bytes())))
       .parse(input)?;
 
@@ -37,6 +37,13 @@ impl Dec {
       }
     }
 
+    // `0.05` keeps only its significant digits, the exponent takes the zeros
+    let leading = data.iter().take_while(|d| **d == 0).count();
+    if leading < data.len() {
+      data.drain(..leading);
+      exponent -= leading as i32;
+    }
+
     while let Some(0) = data.last() {
       data.pop();
     }
@@ -44,6 +51,11 @@ impl Dec {
     let (input, _exponent) = parse_exp(input)?;
     exponent += _exponent;
 
+    // zero has no digits, whatever exponent it was written with
+    if data.is_empty() {
+      exponent = 0;
+    }
+
     #[rustfmt::skip]
     let num = Dec { negative, data, exponent };
 
@@ -61,6 +73,10 @@ impl std::fmt::Display for Dec {
       write!(f, "-")?;
     }
 
+    if self.data.is_empty() {
+      return write!(f, "0");
+    }
+
     if self.is_int() {
       for b in self.data.iter() {
         write!(f, "{b}")?;
@@ -72,13 +88,9 @@ impl std::fmt::Display for Dec {
       return Ok(());
     }
 
-    if self.data.is_empty() {
-      write!(f, "0")?;
-    } else {
-      write!(f, "0.")?;
-      for b in self.data.iter() {
-        write!(f, "{b}")?;
-      }
+    write!(f, "0.")?;
+    for b in self.data.iter() {
+      write!(f, "{b}")?;
     }
 
     if self.exponent != 0 {
@@ -94,13 +106,33 @@ impl std::fmt::Debug for Dec {
   }
 }
 
+// serialized as its `Display` text, a string keeps every digit where a
+// float would round
+#[cfg(feature = "serde")]
+impl serde::Serialize for Dec {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Dec {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Dec, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    match Dec::parse(&text) {
+      Ok(("", num)) => Ok(num),
+      _ => Err(serde::de::Error::custom(format!("invalid number `{text}`"))),
+    }
+  }
+}
+
 fn parse_exp(input: &str) -> nom::IResult<&str, i32> {
   let (input, Some(_)) = opt(char('e').or(char('E'))).parse(input)? else {
     return Ok((input, 0));
   };
 
   let (input, sign) = opt(char('-').or(char('+'))).parse(input)?;
-  let negative = sign.map_or(false, |v| v == '-');
+  let negative = sign == Some('-');
 
   let (input, val) = digit1(input)?;
   let mut val: i32 = val.parse().expect("Num.exponent must have valid digits");
@@ -135,5 +167,55 @@ mod test {
     };
     assert_eq!(num.to_string(), "123");
     assert_eq!(num.data.len(), 3);
+
+    let Ok(("", num)) = Dec::parse("0.0050") else {
+      unreachable!();
+    };
+    assert_eq!(num.to_string(), "0.5e-2");
+    assert_eq!(num.data.len(), 1);
+
+    let Ok(("", num)) = Dec::parse("0.0") else {
+      unreachable!();
+    };
+    assert_eq!(num.to_string(), "0");
+
+    let Ok(("", num)) = Dec::parse("0e5") else {
+      unreachable!();
+    };
+    assert_eq!(num, Dec::parse("0.0e-5").unwrap().1);
+    assert_eq!(num.to_string(), "0");
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde() {
+    for text in [
+      "0",
+      "-12000",
+      "0.5",
+      "-0.12e-3",
+      "3.14159265358979323846264338327950288",
+      "0e5",
+      "0.0e-5",
+      "-0",
+    ] {
+      let Ok(("", num)) = Dec::parse(text) else {
+        unreachable!();
+      };
+      let json = serde_json::to_string(&num).unwrap();
+      assert_eq!(json, format!("\"{num}\""));
+      assert_eq!(serde_json::from_str::<Dec>(&json).unwrap(), num);
+    }
+
+    let json = serde_json::to_string(
+      &Dec::parse("3.14159265358979323846264338327950288")
+        .unwrap()
+        .1,
+    )
+    .unwrap();
+    assert_eq!(json, r#""0.314159265358979323846264338327950288e1""#);
+
+    assert!(serde_json::from_str::<Dec>(r#""1.5x""#).is_err());
+    assert!(serde_json::from_str::<Dec>("1.5").is_err());
   }
 }
@@ -34,16 +34,10 @@ mod test {
     else {
       unreachable!()
     };
-    assert_eq!(
-      obj.get(&"a".to_string()).unwrap(),
-      &JValue::Str("this123".to_string())
-    );
-    assert_eq!(
-      format!("{:?}", obj.get(&"b".to_string()).unwrap()),
-      "Num(Dec(123))"
-    );
-    assert_eq!(obj.get(&"c".to_string()).unwrap(), &JValue::Bool(true));
-    assert_eq!(obj.get(&"d".to_string()).unwrap(), &JValue::Bool(false));
-    assert_eq!(obj.get(&"e".to_string()).unwrap(), &JValue::Null);
+    assert_eq!(obj.get("a").unwrap(), &JValue::Str("this123".to_string()));
+    assert_eq!(format!("{:?}", obj.get("b").unwrap()), "Num(Dec(123))");
+    assert_eq!(obj.get("c").unwrap(), &JValue::Bool(true));
+    assert_eq!(obj.get("d").unwrap(), &JValue::Bool(false));
+    assert_eq!(obj.get("e").unwrap(), &JValue::Null);
   }
 }
@@ -2,7 +2,7 @@ use nom::character::complete::char;
 use nom::error::{Error, ErrorKind, ParseError};
 use nom::{AsChar, IResult, Needed, Parser, Slice};
 
-pub fn raw_str<'a>(raw_input: &'a str) -> IResult<&'a str, String, nom::error::Error<&'a str>> {
+pub fn raw_str(raw_input: &str) -> IResult<&str, String, nom::error::Error<&str>> {
   let (mut input, _) = char('"')(raw_input)?;
 
   let mut text = String::new();
@@ -46,39 +46,34 @@ fn readbable_ascii_char(input: &str) -> IResult<&str, char> {
 
 fn is_readbable_ascii_char(c: char) -> bool {
   let a = c as u32;
-  a >= 0x20 && a <= 0x7E
+  (0x20..=0x7E).contains(&a)
 }
 
-fn scape_char<'a, E: ParseError<&'a str>>(raw_input: &'a str) -> IResult<&str, char, E> {
+fn scape_char<'a, E: ParseError<&'a str>>(raw_input: &'a str) -> IResult<&'a str, char, E> {
   let (input, _) = char::<_, E>('\\')(raw_input)?;
 
   if input.is_empty() {
     return Err(nom::Err::Incomplete(nom::Needed::new(1)));
   }
 
-  match char::<_, E>('n')(input) {
-    Ok((input, _)) => return Ok((input, '\n')),
-    Err(_) => {}
+  if let Ok((input, _)) = char::<_, E>('n')(input) {
+    return Ok((input, '\n'));
   }
 
-  match char::<_, E>('r')(input) {
-    Ok((input, _)) => return Ok((input, '\r')),
-    Err(_) => {}
+  if let Ok((input, _)) = char::<_, E>('r')(input) {
+    return Ok((input, '\r'));
   }
 
-  match char::<_, E>('t')(input) {
-    Ok((input, _)) => return Ok((input, '\t')),
-    Err(_) => {}
+  if let Ok((input, _)) = char::<_, E>('t')(input) {
+    return Ok((input, '\t'));
   }
 
-  match char::<_, E>('\\')(input) {
-    Ok((input, _)) => return Ok((input, '\\')),
-    Err(_) => {}
+  if let Ok((input, _)) = char::<_, E>('\\')(input) {
+    return Ok((input, '\\'));
   }
 
-  match char::<_, E>('"')(input) {
-    Ok((input, _)) => return Ok((input, '\"')),
-    Err(_) => {}
+  if let Ok((input, _)) = char::<_, E>('"')(input) {
+    return Ok((input, '\"'));
   }
 
   Err(nom::Err::Failure(E::from_error_kind(
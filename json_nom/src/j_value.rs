@@ -7,6 +7,7 @@ use crate::{
 use nom::Parser;
 
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum JValue {
   Str(String),
   Num(Dec),
@@ -116,7 +117,7 @@ mod test {
     };
 
     {
-      let Some(JValue::Array(array)) = obj.get(&"a".to_string()) else {
+      let Some(JValue::Array(array)) = obj.get("a") else {
         unreachable!();
       };
       assert_eq!(format!("{:?}", array[0]), "Num(Dec(1))");
@@ -125,16 +126,16 @@ mod test {
       let JValue::Obj(obj1) = &array[2] else {
         unreachable!()
       };
-      let Some(JValue::Bool(true)) = obj1.get(&"x".to_string()) else {
+      let Some(JValue::Bool(true)) = obj1.get("x") else {
         unreachable!()
       };
     }
 
     {
-      let Some(JValue::Obj(b)) = obj.get(&"b".to_string()) else {
+      let Some(JValue::Obj(b)) = obj.get("b") else {
         unreachable!();
       };
-      let Some(JValue::Array(array)) = b.get(&"y".to_string()) else {
+      let Some(JValue::Array(array)) = b.get("y") else {
         unreachable!();
       };
 
@@ -142,4 +143,23 @@ mod test {
       assert_eq!(array, &[Bool(true), Bool(false), Null]);
     }
   }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde() {
+    let text = r#"{"a": [1, "hola", {"x": true}], "b": {"y": [1.10, -0.5e-300, null]}}"#;
+    let Ok(("", value)) = JValue::parse(text) else {
+      unreachable!();
+    };
+    let json = serde_json::to_string(&value).unwrap();
+    assert_eq!(serde_json::from_str::<JValue>(&json).unwrap(), value);
+
+    let Ok(("", value)) = JValue::parse("[12345678901234567890.5]") else {
+      unreachable!();
+    };
+    assert_eq!(
+      serde_json::to_string(&value).unwrap(),
+      r#"{"Array":[{"Num":"0.123456789012345678905e20"}]}"#
+    );
+  }
 }
@@ -1,5 +1,6 @@
 mod commons;
 
+pub mod diagnostics;
 pub mod j_array;
 pub mod j_bool_null;
 pub mod j_num;
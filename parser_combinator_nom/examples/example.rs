@@ -19,4 +19,9 @@ fn main() {
   example("Foo()");
   example("Foo(Bar())");
   example("Foo(Bar(1,2,true),false)");
+  example("1.5");
+  example("null");
+  example(r#"Foo("hola\n", -2.5e3, null)"#);
+  example("1 + 2 * Foo(3)");
+  example("!(1 < 2) || -Foo() == 3");
 }
@@ -0,0 +1,17 @@
+// Both parsers must accept the same inputs, with the same tree, and reject the
+// others at the same offset. Run with `cargo fuzz run differential`.
+
+#![no_main]
+
+use call_ast::{backend::agree, Backend};
+use libfuzzer_sys::fuzz_target;
+use parser_combinator::parser::CombinatorBackend;
+use parser_combinator_nom::NomBackend;
+
+fuzz_target!(|data: &[u8]| {
+  let Ok(code) = std::str::from_utf8(data) else {
+    return;
+  };
+  let (a, b) = (CombinatorBackend.parse(code), NomBackend.parse(code));
+  assert!(agree(&a, &b), "{code:?}: {a:?} vs {b:?}");
+});
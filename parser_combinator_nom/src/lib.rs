@@ -1,31 +1,24 @@
 use nom::{
   branch::alt,
-  bytes::complete::tag,
+  bytes::complete::{tag, take_till, take_while1, take_while_m_n},
   character::{
-    complete::char,
-    complete::{alpha1, alphanumeric0, digit1},
+    complete::{char, one_of, satisfy},
+    complete::{digit0, digit1},
   },
-  combinator::{map, opt},
-  multi::many0,
-  sequence::{delimited, preceded, Tuple},
-  Parser,
+  combinator::{cut, map, map_opt, not, opt, recognize, value},
+  error::{Error, ErrorKind},
+  multi::{many0, many0_count},
+  sequence::{delimited, pair, preceded, terminated, tuple, Tuple},
+  IResult, Parser,
 };
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct Call {
-  target: String,
-  args: Vec<Expr>,
-}
+use call_ast::{Backend, ParseError, BINARY_OPERATORS, TRIVIA, UNARY_OPERATORS};
+use unicode_ident::{is_xid_continue, is_xid_start};
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum Expr {
-  Num(i32),
-  Bool(bool),
-  Call(Call),
-}
+pub use call_ast::{printer, BinOp, Call, Expr, Trivia, UnaryOp};
 
 pub fn parse(input: &str) -> std::result::Result<Expr, String> {
-  let res = expr(input);
+  let res = preceded(trivia(TRIVIA), expr)(input);
   let (_, val) = res.map_err(|f| match f {
     nom::Err::Error(e) => format!("{}", e),
     nom::Err::Failure(e) => format!("{}", e),
@@ -37,8 +30,144 @@ pub fn parse(input: &str) -> std::result::Result<Expr, String> {
   Ok(val)
 }
 
+// `parse` behind the shared `Backend` interface, the error offset is where
+// the failing parser stopped
+pub struct NomBackend;
+
+impl Backend for NomBackend {
+  fn name(&self) -> &str {
+    "nom"
+  }
+
+  fn parse(&self, code: &str) -> std::result::Result<Expr, ParseError> {
+    match preceded(trivia(TRIVIA), expr)(code) {
+      Ok((_, val)) => Ok(val),
+      Err(nom::Err::Error(e) | nom::Err::Failure(e)) => Err(ParseError::new(
+        format!("{:?}", e.code),
+        code.len() - e.input.len(),
+      )),
+      Err(nom::Err::Incomplete(_)) => Err(ParseError::new("Incomplete", code.len())),
+    }
+  }
+}
+
+// skips any amount of trivia, only an unterminated block comment fails
+pub fn trivia(config: Trivia) -> impl FnMut(&str) -> IResult<&str, ()> {
+  move |mut input| loop {
+    if config.whitespace {
+      if let Ok((rest, _)) = take_while1::<_, _, Error<&str>>(char::is_whitespace)(input) {
+        input = rest;
+        continue;
+      }
+    }
+    if config.line_comments {
+      if let Ok((rest, _)) = line_comment(input) {
+        input = rest;
+        continue;
+      }
+    }
+    if config.block_comments {
+      match block_comment(input) {
+        Ok((rest, _)) => {
+          input = rest;
+          continue;
+        }
+        Err(nom::Err::Failure(e)) => return Err(nom::Err::Failure(e)),
+        Err(_) => {}
+      }
+    }
+    return Ok((input, ()));
+  }
+}
+
+fn line_comment(input: &str) -> IResult<&str, &str> {
+  recognize(preceded(tag("//"), take_till(|c| c == '\n')))(input)
+}
+
+// an unterminated comment fails at the end of the input, where `*/` is missing
+fn block_comment(input: &str) -> IResult<&str, &str> {
+  let (rest, _) = tag("/*")(input)?;
+  match rest.find("*/") {
+    Some(end) => Ok((&rest[end + 2..], &input[..end + 4])),
+    None => Err(nom::Err::Failure(Error::new(
+      &rest[rest.len()..],
+      ErrorKind::TakeUntil,
+    ))),
+  }
+}
+
+// a token followed by any trivia
+fn lexeme<'a, O>(
+  parser: impl Parser<&'a str, O, nom::error::Error<&'a str>>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+  terminated(parser, trivia(TRIVIA))
+}
+
+// expr = unary ( binary_op unary ) *
+// see `BINARY_OPERATORS` and `UNARY_OPERATORS` for the precedences
 pub fn expr(input: &str) -> nom::IResult<&str, Expr> {
-  let (input, val) = expr_bool.or(expr_number).or(expr_call).parse(input)?;
+  binary(input, 0)
+}
+
+// precedence climbing, only operators binding at least as tight as
+// `min_precedence` are taken
+fn binary(input: &str, min_precedence: u32) -> nom::IResult<&str, Expr> {
+  let (mut input, mut lhs) = unary(input)?;
+  loop {
+    let mut matched = None;
+    for (token, precedence, op) in BINARY_OPERATORS {
+      if precedence < min_precedence {
+        continue;
+      }
+      match lexeme(tag(token))(input) {
+        Ok((rest, _)) => {
+          matched = Some((rest, precedence, op));
+          break;
+        }
+        Err(nom::Err::Error(_)) => {}
+        Err(e) => return Err(e),
+      }
+    }
+    let Some((rest, precedence, op)) = matched else {
+      return Ok((input, lhs));
+    };
+    // left associative, the right operand only takes tighter operators
+    let (rest, rhs) = cut(|i| binary(i, precedence + 1))(rest)?;
+    input = rest;
+    lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+  }
+}
+
+// unary = primary | unary_op unary
+// a sign right before a number is part of the literal
+fn unary(input: &str) -> nom::IResult<&str, Expr> {
+  match primary(input) {
+    Err(nom::Err::Error(_)) => {}
+    res => return res,
+  }
+  for (token, precedence, op) in UNARY_OPERATORS {
+    match lexeme(tag(token))(input) {
+      Ok((rest, _)) => {
+        let (rest, val) = cut(|i| binary(i, precedence))(rest)?;
+        return Ok((rest, Expr::Unary(op, Box::new(val))));
+      }
+      Err(nom::Err::Error(_)) => {}
+      Err(e) => return Err(e),
+    }
+  }
+  Err(nom::Err::Error(Error::new(input, ErrorKind::Alt)))
+}
+
+// primary = bool | null | float | number | string | call | "(" expr ")"
+fn primary(input: &str) -> nom::IResult<&str, Expr> {
+  let (input, val) = expr_bool
+    .or(expr_null)
+    .or(expr_float)
+    .or(expr_number)
+    .or(expr_string)
+    .or(expr_call)
+    .or(expr_group)
+    .parse(input)?;
   Ok((input, val))
 }
 
@@ -51,60 +180,142 @@ fn bool_literal(input: &str) -> nom::IResult<&str, bool> {
   }
 }
 
+// a word that is not the start of a longer identifier, `nullable()` is a call
+fn keyword<'a, O>(
+  parser: impl Parser<&'a str, O, nom::error::Error<&'a str>>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+  terminated(parser, not(satisfy(is_xid_continue)))
+}
+
 fn expr_bool(input: &str) -> nom::IResult<&str, Expr> {
-  let (input, val) = bool_literal(input)?;
+  let (input, val) = lexeme(keyword(bool_literal))(input)?;
   Ok((input, Expr::Bool(val)))
 }
 
-fn number_literal(input: &str) -> nom::IResult<&str, i32> {
+fn expr_null(input: &str) -> nom::IResult<&str, Expr> {
+  lexeme(keyword(value(Expr::Null, tag("null"))))(input)
+}
+
+// an integer, it must fit in 64 bits
+fn number_literal(raw_input: &str) -> nom::IResult<&str, i64> {
   let mut acc = "".to_string();
   let sign = char('-').or(char('+'));
 
-  let (input, (sign, first)) = (opt(sign), digit1).parse(input)?;
+  let (input, (sign, first)) = (opt(sign), digit1).parse(raw_input)?;
   if let Some(s) = sign {
     acc.push(s);
   }
   acc.push_str(first);
-  let val = match acc.parse::<i32>() {
+  let val = match acc.parse::<i64>() {
     Ok(v) => v,
     Err(_) => {
-      return Err(nom::Err::Error(nom::error::Error::new(
-        input,
-        nom::error::ErrorKind::Digit,
+      return Err(nom::Err::Failure(Error::new(
+        raw_input,
+        ErrorKind::TooLarge,
       )))
     }
   };
   Ok((input, val))
 }
 
+// a number with a fraction, an exponent or both
+fn float_literal(input: &str) -> nom::IResult<&str, f64> {
+  let sign = || opt(one_of("+-"));
+  let exponent = || tuple((one_of("eE"), sign(), digit1));
+  let fraction = tuple((char('.'), digit0, opt(exponent())));
+  let (rest, text) = recognize(tuple((
+    sign(),
+    digit1,
+    alt((recognize(fraction), recognize(exponent()))),
+  )))(input)?;
+  match text.parse::<f64>() {
+    Ok(val) if val.is_finite() => Ok((rest, val)),
+    _ => Err(nom::Err::Failure(Error::new(input, ErrorKind::Float))),
+  }
+}
+
+fn expr_float(input: &str) -> nom::IResult<&str, Expr> {
+  let (input, val) = lexeme(float_literal)(input)?;
+  Ok((input, Expr::Float(val)))
+}
+
+// "..." with the escapes \n \r \t \0 \\ \" and \u{XXXX}
+fn string_literal(input: &str) -> nom::IResult<&str, String> {
+  let (mut input, _) = char('"')(input)?;
+  let mut text = String::new();
+  loop {
+    match input.chars().next() {
+      Some('"') => return Ok((&input[1..], text)),
+      Some('\\') => {
+        let (rest, c) = escape(input)?;
+        input = rest;
+        text.push(c);
+      }
+      Some(c) => {
+        input = &input[c.len_utf8()..];
+        text.push(c);
+      }
+      None => return Err(nom::Err::Failure(Error::new(input, ErrorKind::Char))),
+    }
+  }
+}
+
+fn escape(input: &str) -> nom::IResult<&str, char> {
+  let hex = take_while_m_n(1, 6, |c: char| c.is_ascii_hexdigit());
+  let unicode = map_opt(delimited(tag("\\u{"), hex, char('}')), |hex| {
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+  });
+  let simple = preceded(
+    char('\\'),
+    alt((
+      value('\n', char('n')),
+      value('\r', char('r')),
+      value('\t', char('t')),
+      value('\0', char('0')),
+      value('\\', char('\\')),
+      value('"', char('"')),
+    )),
+  );
+  alt::<_, _, Error<&str>, _>((unicode, simple))(input)
+    .map_err(|_| nom::Err::Failure(Error::new(input, ErrorKind::Escaped)))
+}
+
+fn expr_string(input: &str) -> nom::IResult<&str, Expr> {
+  let (input, val) = lexeme(string_literal)(input)?;
+  Ok((input, Expr::Str(val)))
+}
+
 fn expr_number(input: &str) -> nom::IResult<&str, Expr> {
-  let (input, val) = number_literal(input)?;
+  let (input, val) = lexeme(number_literal)(input)?;
   Ok((input, Expr::Num(val)))
 }
 
-// our regexp to match identifiers
+// ident = ( XID_Start | "_" ) XID_Continue *, Unicode identifiers as in Rust
 fn ident(input: &str) -> nom::IResult<&str, String> {
-  //let re = regex::Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*").unwrap();
-  //ctx.parse_regex(re, "identifier".to_owned())
-  let (input, (first, second)) = alpha1.and(alphanumeric0).parse(input)?;
-  let val = format!("{}{}", first, second);
-  Ok((input, val))
+  let (input, val) = recognize(pair(
+    satisfy(|c| c == '_' || is_xid_start(c)),
+    many0_count(satisfy(is_xid_continue)),
+  ))(input)?;
+  Ok((input, val.to_owned()))
 }
 
+// call = ident "(" args ")"
+// an identifier is only ever a call target, so any failure after it is committed
 fn call(input: &str) -> nom::IResult<&str, Call> {
-  let trailing_arg = preceded(char(','), expr);
+  let trailing_arg = preceded(lexeme(char(',')), cut(expr));
   let non_empty_arg_list = map(expr.and(many0(trailing_arg)), |(head, tail)| {
     let mut vec = vec![head];
     vec.extend_from_slice(&tail);
     vec
   });
-  let arg_list = map(opt(non_empty_arg_list), |v| match v {
-    Some(v) => v,
-    None => vec![],
-  });
+  let arg_list = map(opt(non_empty_arg_list), Option::unwrap_or_default);
 
-  let (input, (target, args)) = (ident, delimited(char('('), arg_list, char(')'))).parse(input)?;
-  Ok((input, Call { target, args }))
+  let (input, (target, args)) = (
+    lexeme(ident),
+    cut(delimited(lexeme(char('(')), arg_list, lexeme(char(')')))),
+  )
+    .parse(input)?;
+  Ok((input, Call::new(target, args)))
 }
 
 fn expr_call(input: &str) -> nom::IResult<&str, Expr> {
@@ -112,10 +323,16 @@ fn expr_call(input: &str) -> nom::IResult<&str, Expr> {
   Ok((input, Expr::Call(val)))
 }
 
+fn expr_group(input: &str) -> nom::IResult<&str, Expr> {
+  delimited(lexeme(char('(')), cut(expr), cut(lexeme(char(')'))))(input)
+}
+
 #[cfg(test)]
 mod test {
   use super::*;
+  use call_ast::{printer::pretty, strategy::arb_expr};
   use nom::error::ErrorKind;
+  use proptest::prelude::*;
 
   #[test]
   fn test_bool_literal() {
@@ -159,6 +376,181 @@ mod test {
     assert_eq!(input, "a");
   }
 
+  #[test]
+  fn test_number_overflow() {
+    let (_, val) = number_literal("9223372036854775807").unwrap();
+    assert_eq!(val, i64::MAX);
+
+    let (_, val) = number_literal("-9223372036854775808").unwrap();
+    assert_eq!(val, i64::MIN);
+
+    let err = number_literal("9223372036854775808,").unwrap_err();
+    let nom::Err::Failure(e) = err else {
+      panic!("Expected failure")
+    };
+    assert_eq!(e.input, "9223372036854775808,");
+    assert_eq!(e.code, ErrorKind::TooLarge);
+  }
+
+  #[test]
+  fn test_float_literal() {
+    assert_eq!(parse("1.5"), Ok(Expr::Float(1.5)));
+    assert_eq!(parse("-0.25"), Ok(Expr::Float(-0.25)));
+    assert_eq!(parse("1."), Ok(Expr::Float(1.0)));
+    assert_eq!(parse("+2e3"), Ok(Expr::Float(2000.0)));
+    assert_eq!(parse("1.5E-1"), Ok(Expr::Float(0.15)));
+
+    let err = float_literal("1e999").unwrap_err();
+    assert!(matches!(err, nom::Err::Failure(_)));
+
+    // plain integers are left to `number_literal`
+    assert!(float_literal("12").is_err());
+  }
+
+  #[test]
+  fn test_string_literal() {
+    assert_eq!(parse(r#""""#), Ok(Expr::Str("".to_string())));
+    assert_eq!(
+      parse(r#""hola, \"mundo\")""#),
+      Ok(Expr::Str("hola, \"mundo\")".to_string()))
+    );
+    assert_eq!(
+      parse(r#""a\nb\tc\\d\0""#),
+      Ok(Expr::Str("a\nb\tc\\d\0".to_string()))
+    );
+    assert_eq!(
+      parse(r#""\u{48}\u{1F600} ñ""#),
+      Ok(Expr::Str("H\u{1F600} ñ".to_string()))
+    );
+
+    let err = string_literal(r#""abc\q""#).unwrap_err();
+    let nom::Err::Failure(e) = err else {
+      panic!("Expected failure")
+    };
+    assert_eq!(e.input, r#"\q""#);
+    assert_eq!(e.code, ErrorKind::Escaped);
+
+    assert!(string_literal(r#""\u{D800}""#).is_err());
+    assert!(matches!(
+      string_literal(r#""abc"#),
+      Err(nom::Err::Failure(_))
+    ));
+  }
+
+  #[test]
+  fn test_null_and_literals_in_call() {
+    assert_eq!(parse("null"), Ok(Expr::Null));
+    assert_eq!(
+      parse(r#"Foo(null, 1.5, "a,b)", 2)"#),
+      Ok(Expr::Call(Call::new(
+        "Foo",
+        vec![
+          Expr::Null,
+          Expr::Float(1.5),
+          Expr::Str("a,b)".to_string()),
+          Expr::Num(2)
+        ]
+      )))
+    );
+  }
+
+  #[test]
+  fn test_operators() {
+    let num = |n| Box::new(Expr::Num(n));
+    let binary = |op, lhs, rhs| Box::new(Expr::Binary(op, lhs, rhs));
+
+    assert_eq!(
+      parse("1 + 2 * Foo(3)"),
+      Ok(*binary(
+        BinOp::Add,
+        num(1),
+        binary(
+          BinOp::Mul,
+          num(2),
+          Box::new(Expr::Call(Call::new("Foo", vec![Expr::Num(3)])))
+        )
+      ))
+    );
+    assert_eq!(
+      parse("(1 + 2) * 3"),
+      Ok(*binary(
+        BinOp::Mul,
+        binary(BinOp::Add, num(1), num(2)),
+        num(3)
+      ))
+    );
+    assert_eq!(
+      parse("1 - 2 - 3"),
+      Ok(*binary(
+        BinOp::Sub,
+        binary(BinOp::Sub, num(1), num(2)),
+        num(3)
+      ))
+    );
+    assert_eq!(
+      parse("1 <= 2 && 3 != 4"),
+      Ok(*binary(
+        BinOp::And,
+        binary(BinOp::Le, num(1), num(2)),
+        binary(BinOp::Ne, num(3), num(4))
+      ))
+    );
+
+    // a sign right before a number is part of the literal
+    assert_eq!(parse("-1"), Ok(Expr::Num(-1)));
+    assert_eq!(parse("1-2"), Ok(*binary(BinOp::Sub, num(1), num(2))));
+    assert_eq!(
+      parse("-Foo() * !true"),
+      Ok(*binary(
+        BinOp::Mul,
+        Box::new(Expr::Unary(
+          UnaryOp::Neg,
+          Box::new(Expr::Call(Call::new("Foo", vec![])))
+        )),
+        Box::new(Expr::Unary(UnaryOp::Not, Box::new(Expr::Bool(true))))
+      ))
+    );
+    assert_eq!(
+      parse("Foo(1 + 2, 3)"),
+      Ok(Expr::Call(Call::new(
+        "Foo",
+        vec![*binary(BinOp::Add, num(1), num(2)), Expr::Num(3)]
+      )))
+    );
+
+    // an operator must be followed by an operand
+    assert!(matches!(expr("1 + "), Err(nom::Err::Failure(_))));
+    assert!(matches!(expr("(1 + 2"), Err(nom::Err::Failure(_))));
+  }
+
+  #[test]
+  fn test_keyword() {
+    assert_eq!(
+      parse("nullable()"),
+      Ok(Expr::Call(Call::new("nullable", vec![])))
+    );
+    // also as arguments, where a keyword prefix used to leave `able()` behind
+    assert_eq!(
+      parse("Foo(nullable(), trueish(), false_())"),
+      Ok(Expr::Call(Call::new(
+        "Foo",
+        vec![
+          Expr::Call(Call::new("nullable", vec![])),
+          Expr::Call(Call::new("trueish", vec![])),
+          Expr::Call(Call::new("false_", vec![])),
+        ]
+      )))
+    );
+    assert_eq!(
+      parse("true && false"),
+      Ok(Expr::Binary(
+        BinOp::And,
+        Box::new(Expr::Bool(true)),
+        Box::new(Expr::Bool(false))
+      ))
+    );
+  }
+
   #[test]
   fn test_ident() {
     let (input, val) = ident("foo").unwrap();
@@ -175,26 +567,79 @@ mod test {
     let (input, val) = ident("foo2A3dEz(").unwrap();
     assert_eq!(val, "foo2A3dEz");
     assert_eq!(input, "(");
+
+    let (input, val) = ident("_foo_1 ").unwrap();
+    assert_eq!(val, "_foo_1");
+    assert_eq!(input, " ");
+    assert!(ident("1foo").is_err());
+
+    let (input, val) = ident("名前_ñ(").unwrap();
+    assert_eq!(val, "名前_ñ");
+    assert_eq!(input, "(");
+    assert!(ident("\u{301}e").is_err());
+  }
+
+  #[test]
+  fn test_trivia() {
+    let (input, _) = trivia(TRIVIA)("\u{a0}\u{b}// comment\n/* block\n comment */ 1").unwrap();
+    assert_eq!(input, "1");
+
+    // the error of an unterminated comment points at the end of the input
+    let Err(nom::Err::Failure(e)) = trivia(TRIVIA)(" /* open") else {
+      unreachable!();
+    };
+    assert_eq!((e.input, e.code), ("", ErrorKind::TakeUntil));
+
+    let comments = Trivia {
+      line_comments: true,
+      ..Trivia::NONE
+    };
+    let (input, _) = trivia(comments)("// one\n// two\n 1").unwrap();
+    assert_eq!(input, "\n// two\n 1");
+  }
+
+  #[test]
+  fn test_parse_with_trivia() {
+    let expected = Expr::Call(Call::new("Foo", vec![Expr::Num(1)]));
+    assert_eq!(parse("/**/Foo // c\n(\t1/**/)\n"), Ok(expected.clone()));
+    assert_eq!(lexeme(tag("Foo"))("Foo /* c */ (1)"), Ok(("(1)", "Foo")));
+    let err = NomBackend.parse("Foo(1 /* 2) ").unwrap_err();
+    assert_eq!(err.offset, 12);
   }
 
   #[test]
   fn test_call() {
     let (input, val) = call("foo()").unwrap();
     assert_eq!(input, "");
-    assert_eq!(val.target, "foo");
-    assert_eq!(val.args.len(), 0);
+    assert_eq!(val.target(), "foo");
+    assert_eq!(val.args().len(), 0);
 
     let (input, val) = call("Foo(Bar(1,2,true),false)").unwrap();
     assert_eq!(input, "");
-    assert_eq!(val.target, "Foo");
-    assert_eq!(val.args.len(), 2);
+    assert_eq!(val.target(), "Foo");
+    assert_eq!(val.args().len(), 2);
     assert_eq!(
-      val.args[0],
-      Expr::Call(Call {
-        target: "Bar".to_string(),
-        args: vec![Expr::Num(1), Expr::Num(2), Expr::Bool(true)]
-      })
+      val.args()[0],
+      Expr::Call(Call::new(
+        "Bar",
+        vec![Expr::Num(1), Expr::Num(2), Expr::Bool(true)]
+      ))
     );
-    assert_eq!(val.args[1], Expr::Bool(false));
+    assert_eq!(val.args()[1], Expr::Bool(false));
+  }
+
+  #[test]
+  fn test_backend() {
+    assert_eq!(NomBackend.parse("Foo(1)").ok(), parse("Foo(1)").ok());
+    let err = NomBackend.parse("Foo(1, @)").unwrap_err();
+    assert_eq!(err.offset, 7);
+  }
+
+  proptest! {
+    #[test]
+    fn test_round_trip(expr in arb_expr(), width in 0usize..60) {
+      prop_assert_eq!(parse(&expr.to_string()), Ok(expr.clone()));
+      prop_assert_eq!(parse(&pretty(&expr, width)), Ok(expr));
+    }
   }
 }
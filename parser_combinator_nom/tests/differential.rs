@@ -0,0 +1,130 @@
+// Both parsers must agree on every input: the same tree, or a failure at the
+// same offset.
+
+use call_ast::{
+  backend::{agree, differential},
+  printer::pretty,
+  strategy::{arb_expr, arb_mutated, arb_source},
+  Backend,
+};
+use parser_combinator::parser::CombinatorBackend;
+use parser_combinator_nom::NomBackend;
+use proptest::prelude::*;
+
+const VALID: &[&str] = &[
+  "1",
+  "-1",
+  "+12",
+  "1.5",
+  "-2.5e3",
+  "1e-2",
+  "9223372036854775807",
+  r#""""#,
+  r#""a\n\t\"\\ \u{1F600}""#,
+  "true",
+  "false",
+  "null",
+  "nullable()",
+  "true_()",
+  "_foo()",
+  "Foo()",
+  "Foo(1, 2)",
+  "Foo(Bar(1, 2, true), false)",
+  r#"Foo(null, 1.5, "a,b)", 2)"#,
+  "1 + 2 * 3",
+  "(1 + 2) * 3",
+  "1 - 2 - 3",
+  "1-2",
+  "1 <= 2 && 3 != 4 || !false",
+  "-Foo() * !true",
+  "--1",
+  "!!Foo(1 % 2 / 3)",
+  "  Foo (\n  1 ,\n  2\n)\n",
+  "// call\nFoo(1, /* two */ 2) // done",
+  "\u{a0}Foo(\u{b}1,\r\n2)\u{3000}",
+  "Foo() trailing",
+  "名前(ñandú(), e\u{301}())",
+  "truẽ()",
+];
+
+const INVALID: &[&str] = &[
+  "",
+  "   ",
+  "@",
+  "Foo",
+  "Foo(",
+  "Foo(1",
+  "Foo(1,",
+  "Foo(1,)",
+  "Foo(1 2)",
+  "Foo(1, @)",
+  "Foo(Bar(1, )",
+  "1 +",
+  "1 + ",
+  "1 + * 2",
+  "(1 + 2",
+  "()",
+  "-",
+  "!",
+  "Foo(1 /* 2) ",
+  "/* open",
+  r#""open"#,
+  r#""\q""#,
+  "99999999999999999999",
+  "1e999",
+  "\u{301}()",
+  "Foo(€)",
+];
+
+#[test]
+fn test_corpus() {
+  let backends: [&dyn Backend; 2] = [&CombinatorBackend, &NomBackend];
+  let corpus: Vec<&str> = VALID.iter().chain(INVALID).copied().collect();
+  let mismatches = differential(&backends, &corpus);
+  let report: String = mismatches.iter().map(|m| m.to_string()).collect();
+  assert!(mismatches.is_empty(), "{report}");
+
+  for code in VALID {
+    assert!(NomBackend.parse(code).is_ok(), "{code:?} should parse");
+  }
+  for code in INVALID {
+    assert!(NomBackend.parse(code).is_err(), "{code:?} should fail");
+  }
+}
+
+proptest! {
+  #[test]
+  fn test_generated(expr in arb_expr(), width in 0usize..60) {
+    for code in [expr.to_string(), pretty(&expr, width)] {
+      prop_assert_eq!(CombinatorBackend.parse(&code), Ok(expr.clone()));
+      prop_assert_eq!(NomBackend.parse(&code), Ok(expr.clone()));
+    }
+  }
+
+  // cutting a valid input short must fail at the same place in both
+  #[test]
+  fn test_truncated(expr in arb_expr(), cut in any::<prop::sample::Index>()) {
+    let code = expr.to_string();
+    let mut end = cut.index(code.len() + 1);
+    while !code.is_char_boundary(end) {
+      end -= 1;
+    }
+    let code = &code[..end];
+    let (a, b) = (CombinatorBackend.parse(code), NomBackend.parse(code));
+    prop_assert!(agree(&a, &b), "{:?}: {:?} vs {:?}", code, a, b);
+  }
+
+  // any spelling of a tree parses to it, in both
+  #[test]
+  fn test_source((code, expr) in arb_source()) {
+    prop_assert_eq!(CombinatorBackend.parse(&code), Ok(expr.clone()), "{:?}", code);
+    prop_assert_eq!(NomBackend.parse(&code), Ok(expr), "{:?}", code);
+  }
+
+  // broken inputs are rejected by both, at the same place
+  #[test]
+  fn test_mutated(code in arb_mutated()) {
+    let (a, b) = (CombinatorBackend.parse(&code), NomBackend.parse(&code));
+    prop_assert!(agree(&a, &b), "{:?}: {:?} vs {:?}", code, a, b);
+  }
+}
@@ -0,0 +1,123 @@
+// Compares the shared-text `Ctx` against the previous representation, which
+// cloned the whole input `String` every time a parser advanced, and times
+// regex misses, which grow with the input size only.
+//
+// cargo bench --bench ctx
+
+use parser_combinator::foundation::{Ctx, Regex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+struct OwnedCtx {
+  text: String,
+  index: usize,
+}
+
+impl OwnedCtx {
+  fn new(text: &str) -> Self {
+    OwnedCtx {
+      text: text.to_string(),
+      index: 0,
+    }
+  }
+
+  fn skip(&self, count: usize) -> Self {
+    OwnedCtx {
+      text: self.text.clone(),
+      index: (self.index + count).min(self.text.len()),
+    }
+  }
+
+  fn parse_str(&self, r#match: &str) -> Option<Self> {
+    if self.text[self.index..].starts_with(r#match) {
+      Some(self.skip(r#match.len()))
+    } else {
+      None
+    }
+  }
+}
+
+// "1,1,1,...,1" with `count` items
+fn generate_list(count: usize) -> String {
+  vec!["1"; count].join(",")
+}
+
+// "Foo(1,Foo(1,...),true)" nested `depth` times with `width` numbers per call
+fn generate_call(depth: usize, width: usize) -> String {
+  let mut code = String::from("true");
+  for _ in 0..depth {
+    let nums = vec!["1"; width].join(",");
+    code = format!("Foo({nums},{code})");
+  }
+  code
+}
+
+fn scan_owned(text: &str) -> usize {
+  let mut ctx = OwnedCtx::new(text);
+  let mut count = 0;
+  while let Some(next) = ctx.parse_str("1") {
+    count += 1;
+    ctx = match next.parse_str(",") {
+      Some(next) => next,
+      None => break,
+    };
+  }
+  count
+}
+
+fn scan_shared(text: &str) -> usize {
+  let mut ctx = Ctx::new(text);
+  let mut count = 0;
+  while let Ok(success) = ctx.parse_str("1") {
+    count += 1;
+    ctx = match success.ctx().parse_str(",") {
+      Ok(success) => success.ctx().to_owned(),
+      Err(_) => break,
+    };
+  }
+  count
+}
+
+// tries `re` at every position of "aaa...a", where nothing matches
+fn regex_misses(text: &str, re: &Regex) -> usize {
+  let ctx = Ctx::new(text);
+  (0..text.len())
+    .step_by(100)
+    .filter(|index| ctx.skip(*index).parse_regex(re, String::new()).is_err())
+    .count()
+}
+
+fn measure<T>(name: &str, size: usize, iterations: u32, f: impl Fn() -> T) {
+  let mut total = Duration::ZERO;
+  for _ in 0..iterations {
+    let start = Instant::now();
+    std::hint::black_box(f());
+    total += start.elapsed();
+  }
+  println!(
+    "{name:<24} {size:>10} bytes {:>12.3?} / iter",
+    total / iterations
+  );
+}
+
+fn main() {
+  for count in [1_000, 10_000, 50_000] {
+    let text = generate_list(count);
+    measure("scan owned String", text.len(), 5, || scan_owned(&text));
+    measure("scan shared Ctx", text.len(), 5, || scan_shared(&text));
+  }
+
+  for width in [100, 1_000, 2_000] {
+    let code = generate_call(10, width);
+    measure("parser::parse", code.len(), 5, || {
+      parser_combinator::parser::parse(&code).unwrap()
+    });
+  }
+
+  // `parse_regex` searches anchored, the pattern needs no `\A`
+  let re = Regex::new(r"[0-9]+").unwrap();
+  for size in [10_000, 100_000, 1_000_000] {
+    let text = "a".repeat(size);
+    measure("regex misses", size, 5, || regex_misses(&text, &re));
+  }
+}
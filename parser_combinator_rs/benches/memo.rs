@@ -0,0 +1,69 @@
+// Packrat parsing on a grammar that backtracks exponentially without it,
+// `memo` makes every rule run once per position.
+//
+// cargo bench --bench memo
+
+use parser_combinator::{
+  combinator::{delimited, memo},
+  foundation::{Ctx, Result},
+  Parser,
+};
+use std::time::{Duration, Instant};
+
+// a = "(" a ")" "x" | "(" a ")" "y" | "z"
+fn nested(ctx: &Ctx, a: fn(&Ctx) -> Result<usize>) -> Result<usize> {
+  let token = |token: &'static str| move |ctx: &Ctx| ctx.parse_str(token);
+  let group = |end| delimited(token("("), a, token(")")).skip(token(end));
+  let leaf = token("z").map(|_| 0);
+  group("x")
+    .or(group("y"))
+    .map(|depth| depth + 1)
+    .or(leaf)
+    .parse(ctx)
+}
+
+fn plain(ctx: &Ctx) -> Result<usize> {
+  nested(ctx, plain)
+}
+
+fn memoized(ctx: &Ctx) -> Result<usize> {
+  memo(|ctx: &Ctx| nested(ctx, memoized))(ctx)
+}
+
+// "((...z)y)y" nested `depth` times, the worst case for the first alternative
+fn generate(depth: usize) -> String {
+  format!("{}z{}", "(".repeat(depth), ")y".repeat(depth))
+}
+
+fn measure<T>(name: &str, size: usize, iterations: u32, f: impl Fn() -> T) {
+  let mut total = Duration::ZERO;
+  for _ in 0..iterations {
+    let start = Instant::now();
+    std::hint::black_box(f());
+    total += start.elapsed();
+  }
+  println!(
+    "{name:<24} {size:>10} bytes {:>12.3?} / iter",
+    total / iterations
+  );
+}
+
+fn main() {
+  for depth in [10, 15, 20] {
+    let code = generate(depth);
+    measure("backtracking", code.len(), 5, || {
+      plain(&Ctx::new(&code)).unwrap()
+    });
+    measure("memo", code.len(), 5, || {
+      memoized(&Ctx::new(&code)).unwrap()
+    });
+  }
+
+  // beyond what backtracking can do, the time grows with the input
+  for depth in [100, 1_000, 2_000] {
+    let code = generate(depth);
+    measure("memo", code.len(), 5, || {
+      memoized(&Ctx::new(&code)).unwrap()
+    });
+  }
+}
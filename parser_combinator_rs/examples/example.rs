@@ -1,4 +1,7 @@
-use parser_combinator::parser::parse;
+use parser_combinator::{
+  eval::Runtime,
+  parser::{parse, parse_with_diagnostic, parse_with_recovery},
+};
 
 fn example(code: impl AsRef<str>) {
   let res = parse(code.as_ref());
@@ -8,6 +11,27 @@ fn example(code: impl AsRef<str>) {
   }
 }
 
+fn report(code: &str) {
+  if let Err(diagnostic) = parse_with_diagnostic(code) {
+    println!("{}", diagnostic.render(code, true));
+  }
+}
+
+fn report_all(code: &str) {
+  let (expr, diagnostics) = parse_with_recovery(code);
+  println!("partial: {:?}", expr);
+  for diagnostic in diagnostics {
+    println!("{}", diagnostic.render(code, true));
+  }
+}
+
+fn evaluate(code: &str) {
+  match Runtime::new().eval_str(code) {
+    Ok(val) => println!("{} = {}", code, val),
+    Err(err) => println!("{} failed: {}", code, err),
+  }
+}
+
 fn main() {
   example("true");
   example("false");
@@ -19,4 +43,16 @@ fn main() {
   example("Foo()");
   example("Foo(Bar())");
   example("Foo(Bar(1,2,true),false)");
+  example("1.5");
+  example("null");
+  example(r#"Foo("hola\n", -2.5e3, null)"#);
+  example("1 + 2 * Foo(3)");
+  example("!(1 < 2) || -Foo() == 3");
+
+  report("Foo(Bar(1,2;true),false)");
+  report_all("Foo(1,@,Bar(true,),3)");
+
+  evaluate("1 + 2 * Mul(3, 4)");
+  evaluate(r#"If(1 < 2, "yes", "no")"#);
+  evaluate("Add(1, true)");
 }
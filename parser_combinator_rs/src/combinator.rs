@@ -1,123 +1,1645 @@
-use crate::foundation::{Ctx, Result};
-use std::cell::Cell;
+use crate::{
+  diagnostics::Diagnostic,
+  foundation::{Ctx, Failure, MemoId, Regex, Result},
+  trace::Outcome,
+};
+pub use call_ast::Trivia;
+use regex_automata::PatternID;
+use std::{
+  any::TypeId,
+  sync::atomic::{AtomicUsize, Ordering},
+};
 
 pub type ParserFn<T> = Box<dyn Fn(&Ctx) -> Result<T>>;
 
+// Every `Fn(&Ctx) -> Result<T>` is a parser, so plain functions, closures and
+// `ParserFn` can be chained with the methods below.
+pub trait Parser<T> {
+  fn parse(&self, ctx: &Ctx) -> Result<T>;
+
+  fn map<U>(self, op: impl Fn(T) -> U) -> impl Fn(&Ctx) -> Result<U>
+  where
+    Self: Sized,
+  {
+    move |ctx| Ok(self.parse(ctx)?.map(&op))
+  }
+
+  // runs the parser returned by `op` right after this one
+  fn and_then<U, P: Parser<U>>(self, op: impl Fn(T) -> P) -> impl Fn(&Ctx) -> Result<U>
+  where
+    Self: Sized,
+  {
+    move |ctx| {
+      let (next_ctx, val) = self.parse(ctx)?.into_parts();
+      let (next_ctx, val) = op(val).parse(&next_ctx)?.into_parts();
+      Ok(next_ctx.success_from(ctx.index(), val))
+    }
+  }
+
+  // tries `other` from the same position when this parser fails
+  fn or(self, other: impl Parser<T>) -> impl Fn(&Ctx) -> Result<T>
+  where
+    Self: Sized,
+  {
+    move |ctx| {
+      let err = match self.parse(ctx) {
+        Ok(success) => return Ok(success),
+        Err(failure) if failure.is_committed() => return Err(failure),
+        Err(failure) => failure,
+      };
+      match other.parse(ctx) {
+        Ok(success) => Ok(success),
+        Err(failure) if failure.is_committed() => Err(failure),
+        Err(failure) => Err(err.merge(failure)),
+      }
+    }
+  }
+
+  fn then<U>(self, other: impl Parser<U>) -> impl Fn(&Ctx) -> Result<(T, U)>
+  where
+    Self: Sized,
+  {
+    move |ctx| {
+      let (next_ctx, left) = self.parse(ctx)?.into_parts();
+      let (next_ctx, right) = other.parse(&next_ctx)?.into_parts();
+      Ok(next_ctx.success_from(ctx.index(), (left, right)))
+    }
+  }
+
+  // parses `other` after this parser and keeps only this parser's value
+  fn skip<U>(self, other: impl Parser<U>) -> impl Fn(&Ctx) -> Result<T>
+  where
+    Self: Sized,
+  {
+    move |ctx| {
+      let (next_ctx, val) = self.parse(ctx)?.into_parts();
+      let success = other.parse(&next_ctx)?;
+      Ok(success.ctx().success_from(ctx.index(), val))
+    }
+  }
+
+  fn optional(self) -> impl Fn(&Ctx) -> Result<Option<T>>
+  where
+    Self: Sized,
+  {
+    move |ctx| match self.parse(ctx) {
+      Ok(success) => Ok(success.map(Some)),
+      Err(failure) if failure.is_committed() => Err(failure),
+      Err(_) => Ok(ctx.success(None)),
+    }
+  }
+
+  fn many(self) -> impl Fn(&Ctx) -> Result<Vec<T>>
+  where
+    Self: Sized,
+  {
+    many(move |ctx: &Ctx| self.parse(ctx))
+  }
+
+  fn sep_by<S>(self, separator: impl Parser<S>) -> impl Fn(&Ctx) -> Result<Vec<T>>
+  where
+    Self: Sized,
+  {
+    separated(
+      move |ctx: &Ctx| separator.parse(ctx),
+      move |ctx: &Ctx| self.parse(ctx),
+    )
+  }
+
+  fn sep_by1<S>(self, separator: impl Parser<S>) -> impl Fn(&Ctx) -> Result<Vec<T>>
+  where
+    Self: Sized,
+  {
+    separated1(
+      move |ctx: &Ctx| separator.parse(ctx),
+      move |ctx: &Ctx| self.parse(ctx),
+    )
+  }
+
+  fn sep_by_trailing<S>(self, separator: impl Parser<S>) -> impl Fn(&Ctx) -> Result<Vec<T>>
+  where
+    Self: Sized,
+  {
+    separated_trailing(
+      move |ctx: &Ctx| separator.parse(ctx),
+      move |ctx: &Ctx| self.parse(ctx),
+    )
+  }
+
+  fn cut(self) -> impl Fn(&Ctx) -> Result<T>
+  where
+    Self: Sized,
+  {
+    cut(move |ctx: &Ctx| self.parse(ctx))
+  }
+
+  // replaces the expected message when the parser fails without consuming input
+  fn label(self, expected: &str) -> impl Fn(&Ctx) -> Result<T>
+  where
+    Self: Sized,
+  {
+    let expected = expected.to_owned();
+    move |ctx| match self.parse(ctx) {
+      Err(failure) if failure.index() == ctx.index() => Err(failure.relabel(expected.clone())),
+      res => res,
+    }
+  }
+
+  fn named(self, name: &str) -> impl Fn(&Ctx) -> Result<T>
+  where
+    Self: Sized,
+  {
+    named(name, move |ctx: &Ctx| self.parse(ctx))
+  }
+}
+
+impl<T, F: Fn(&Ctx) -> Result<T>> Parser<T> for F {
+  fn parse(&self, ctx: &Ctx) -> Result<T> {
+    self(ctx)
+  }
+}
+
 pub fn any<T>(parsers: Vec<ParserFn<T>>) -> impl Fn(&Ctx) -> Result<T> {
   fn _parser<T>(ctx: &Ctx, parsers: &Vec<ParserFn<T>>) -> Result<T> {
-    let mut err = ctx.failure("any".to_string());
+    let mut err: Option<Failure> = None;
 
     for parser in parsers {
       let res = parser(ctx);
       let failure = match res {
         Ok(success) => return Ok(success),
+        Err(failure) if failure.is_committed() => return Err(failure),
         Err(failure) => failure,
       };
-      if failure.index() > err.index() {
-        err = failure;
-      }
+      err = Some(match err {
+        Some(err) => err.merge(failure),
+        None => failure,
+      });
     }
-    Err(err)
+    Err(err.unwrap_or_else(|| ctx.failure("any".to_string())))
   }
 
   move |ctx| _parser(ctx, &parsers)
 }
 
-// pub fn sequence<T: Clone>(parsers: Vec<ParserFn<T>>) -> impl Fn(&Ctx) -> Result<Vec<T>> {
-//   fn _parser<T: Clone>(ctx: &Ctx, parsers: &Vec<ParserFn<T>>) -> Result<Vec<T>> {
-//     let mut values: Vec<T> = vec![];
-//     let mut next_ctx = ctx.to_owned();
-//     for parser in parsers {
-//       let success = parser(&next_ctx)?;
-//       next_ctx = success.ctx().to_owned();
-//       let val = success.val();
-//       values.push(val);
-//     }
-//     Ok(next_ctx.success(values))
-//   }
-//   move |ctx| _parser(ctx, &parsers)
-// }
-
-pub fn many<T: Clone>(parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<Vec<T>> {
+pub fn sequence<T>(parsers: Vec<ParserFn<T>>) -> impl Fn(&Ctx) -> Result<Vec<T>> {
+  fn _parser<T>(ctx: &Ctx, parsers: &Vec<ParserFn<T>>) -> Result<Vec<T>> {
+    let mut values: Vec<T> = vec![];
+    let mut next_ctx = ctx.to_owned();
+    for parser in parsers {
+      let (ctx, val) = parser(&next_ctx)?.into_parts();
+      next_ctx = ctx;
+      values.push(val);
+    }
+    Ok(next_ctx.success_from(ctx.index(), values))
+  }
+  move |ctx| _parser(ctx, &parsers)
+}
+
+// A tuple of parsers run one after the other, see `tuple`
+pub trait Tuple<T> {
+  fn parse_tuple(&self, ctx: &Ctx) -> Result<T>;
+}
+
+macro_rules! tuple_impl {
+  ($($P:ident $p:ident $V:ident $v:ident),+) => {
+    impl<$($P: Parser<$V>, $V),+> Tuple<($($V,)+)> for ($($P,)+) {
+      fn parse_tuple(&self, ctx: &Ctx) -> Result<($($V,)+)> {
+        let ($($p,)+) = self;
+        let next_ctx = ctx.to_owned();
+        $(let (next_ctx, $v) = $p.parse(&next_ctx)?.into_parts();)+
+        Ok(next_ctx.success_from(ctx.index(), ($($v,)+)))
+      }
+    }
+  };
+}
+
+tuple_impl!(P1 p1 V1 v1, P2 p2 V2 v2);
+tuple_impl!(P1 p1 V1 v1, P2 p2 V2 v2, P3 p3 V3 v3);
+tuple_impl!(P1 p1 V1 v1, P2 p2 V2 v2, P3 p3 V3 v3, P4 p4 V4 v4);
+tuple_impl!(P1 p1 V1 v1, P2 p2 V2 v2, P3 p3 V3 v3, P4 p4 V4 v4, P5 p5 V5 v5);
+tuple_impl!(P1 p1 V1 v1, P2 p2 V2 v2, P3 p3 V3 v3, P4 p4 V4 v4, P5 p5 V5 v5, P6 p6 V6 v6);
+tuple_impl!(
+  P1 p1 V1 v1, P2 p2 V2 v2, P3 p3 V3 v3, P4 p4 V4 v4, P5 p5 V5 v5, P6 p6 V6 v6, P7 p7 V7 v7
+);
+tuple_impl!(
+  P1 p1 V1 v1, P2 p2 V2 v2, P3 p3 V3 v3, P4 p4 V4 v4, P5 p5 V5 v5, P6 p6 V6 v6, P7 p7 V7 v7,
+  P8 p8 V8 v8
+);
+tuple_impl!(
+  P1 p1 V1 v1, P2 p2 V2 v2, P3 p3 V3 v3, P4 p4 V4 v4, P5 p5 V5 v5, P6 p6 V6 v6, P7 p7 V7 v7,
+  P8 p8 V8 v8, P9 p9 V9 v9
+);
+tuple_impl!(
+  P1 p1 V1 v1, P2 p2 V2 v2, P3 p3 V3 v3, P4 p4 V4 v4, P5 p5 V5 v5, P6 p6 V6 v6, P7 p7 V7 v7,
+  P8 p8 V8 v8, P9 p9 V9 v9, P10 p10 V10 v10
+);
+tuple_impl!(
+  P1 p1 V1 v1, P2 p2 V2 v2, P3 p3 V3 v3, P4 p4 V4 v4, P5 p5 V5 v5, P6 p6 V6 v6, P7 p7 V7 v7,
+  P8 p8 V8 v8, P9 p9 V9 v9, P10 p10 V10 v10, P11 p11 V11 v11
+);
+tuple_impl!(
+  P1 p1 V1 v1, P2 p2 V2 v2, P3 p3 V3 v3, P4 p4 V4 v4, P5 p5 V5 v5, P6 p6 V6 v6, P7 p7 V7 v7,
+  P8 p8 V8 v8, P9 p9 V9 v9, P10 p10 V10 v10, P11 p11 V11 v11, P12 p12 V12 v12
+);
+
+// runs a tuple of 2 to 12 parsers in order, collecting their values in a tuple
+pub fn tuple<T>(parsers: impl Tuple<T>) -> impl Fn(&Ctx) -> Result<T> {
+  move |ctx| parsers.parse_tuple(ctx)
+}
+
+pub fn map<T, R>(
+  parser: impl Fn(&Ctx) -> Result<T>,
+  op: impl Fn(T) -> R,
+) -> impl Fn(&Ctx) -> Result<R> {
+  move |ctx| Ok(parser(ctx)?.map(&op))
+}
+
+// like `map` but `op` can reject the value, the failure points to where
+// `parser` started and expects the error message
+pub fn map_res<T, R, E: std::fmt::Display>(
+  parser: impl Fn(&Ctx) -> Result<T>,
+  op: impl Fn(T) -> std::result::Result<R, E>,
+) -> impl Fn(&Ctx) -> Result<R> {
+  move |ctx| {
+    let success = parser(ctx)?;
+    let span = success.span();
+    let (next_ctx, val) = success.into_parts();
+    match op(val) {
+      Ok(val) => Ok(next_ctx.success_from(span.start, val)),
+      Err(err) => Err(ctx.failure(err.to_string())),
+    }
+  }
+}
+
+pub fn value<T: Clone, U>(
+  val: T,
+  parser: impl Fn(&Ctx) -> Result<U>,
+) -> impl Fn(&Ctx) -> Result<T> {
+  move |ctx| Ok(parser(ctx)?.map(|_| val.clone()))
+}
+
+pub fn opt<T>(parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<Option<T>> {
+  move |ctx| match parser(ctx) {
+    Ok(success) => Ok(success.map(Some)),
+    Err(failure) if failure.is_committed() => Err(failure),
+    Err(_) => Ok(ctx.success(None)),
+  }
+}
+
+// succeeds without consuming input only when `parser` fails
+pub fn not<T>(parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<()> {
+  move |ctx| match parser(ctx) {
+    Ok(success) => {
+      let found = &ctx.text()[ctx.index()..success.ctx().index()];
+      Err(ctx.failure(format!("anything but `{found}`")))
+    }
+    Err(failure) if failure.is_committed() => Err(failure),
+    Err(_) => Ok(ctx.success(())),
+  }
+}
+
+// runs `parser` without consuming input
+pub fn peek<T>(parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<T> {
+  move |ctx| Ok(ctx.success(parser(ctx)?.into_val()))
+}
+
+pub fn eof(ctx: &Ctx) -> Result<()> {
+  if ctx.text_slice().is_empty() {
+    Ok(ctx.success(()))
+  } else {
+    Err(ctx.failure("end of input".to_owned()))
+  }
+}
+
+// the next char, whatever it is
+pub fn any_char(ctx: &Ctx) -> Result<char> {
+  satisfy(|_| true, "any character")(ctx)
+}
+
+// the next char when `pred` accepts it
+pub fn satisfy(pred: impl Fn(char) -> bool, expected: &str) -> impl Fn(&Ctx) -> Result<char> {
+  let expected = expected.to_owned();
+  move |ctx| match ctx.text_slice().chars().next() {
+    Some(c) if pred(c) => Ok(ctx.skip(c.len_utf8()).success_from(ctx.index(), c)),
+    _ => Err(ctx.failure(expected.clone())),
+  }
+}
+
+// the next char when it is in `class`, written like a regex class without
+// the brackets: chars and `a-z` ranges, a leading `^` negates it and a `-`
+// first or last is taken literally
+pub fn char_class(class: &str) -> impl Fn(&Ctx) -> Result<char> {
+  let (negated, items) = match class.strip_prefix('^') {
+    Some(items) => (true, items),
+    None => (false, class),
+  };
+  let chars: Vec<char> = items.chars().collect();
+  let mut ranges = vec![];
+  let mut i = 0;
+  while i < chars.len() {
+    if i + 2 < chars.len() && chars[i + 1] == '-' {
+      ranges.push(chars[i]..=chars[i + 2]);
+      i += 3;
+    } else {
+      ranges.push(chars[i]..=chars[i]);
+      i += 1;
+    }
+  }
+  let in_class = move |c| ranges.iter().any(|range| range.contains(&c)) != negated;
+  satisfy(in_class, &format!("[{class}]"))
+}
+
+// the longest of `words` at the current position, looked up in a byte trie
+// so the text is scanned once whatever the number of words
+pub fn one_of_str(words: &[&str]) -> impl Fn(&Ctx) -> Result<String> {
+  assert!(!words.is_empty(), "one_of_str needs at least one word");
+  let mut trie = vec![TrieNode::default()];
+  for word in words {
+    let mut node = 0;
+    for &byte in word.as_bytes() {
+      node = match trie[node].child(byte) {
+        Some(child) => child,
+        None => {
+          trie.push(TrieNode::default());
+          let child = trie.len() - 1;
+          trie[node].children.push((byte, child));
+          child
+        }
+      };
+    }
+    trie[node].terminal = true;
+  }
+  let words: Vec<String> = words.iter().map(|word| word.to_string()).collect();
+  move |ctx| {
+    let text = ctx.text_slice().as_bytes();
+    let mut node = 0;
+    let mut longest = trie[0].terminal.then_some(0);
+    for (i, &byte) in text.iter().enumerate() {
+      match trie[node].child(byte) {
+        Some(child) => node = child,
+        None => break,
+      }
+      if trie[node].terminal {
+        longest = Some(i + 1);
+      }
+    }
+    match longest {
+      Some(len) => {
+        let val = ctx.text_slice()[..len].to_owned();
+        Ok(ctx.skip(len).success_from(ctx.index(), val))
+      }
+      None => {
+        let failures = words.iter().map(|word| ctx.failure(word.clone()));
+        Err(failures.reduce(Failure::merge).unwrap())
+      }
+    }
+  }
+}
+
+#[derive(Default)]
+struct TrieNode {
+  children: Vec<(u8, usize)>,
+  terminal: bool,
+}
+
+impl TrieNode {
+  fn child(&self, byte: u8) -> Option<usize> {
+    self
+      .children
+      .iter()
+      .find(|(b, _)| *b == byte)
+      .map(|(_, child)| *child)
+  }
+}
+
+// the text consumed by `parser` instead of its value
+pub fn recognize<T>(parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<String> {
+  move |ctx| {
+    let (next_ctx, _) = parser(ctx)?.into_parts();
+    let text = ctx.text()[ctx.index()..next_ctx.index()].to_owned();
+    Ok(next_ctx.success_from(ctx.index(), text))
+  }
+}
+
+// What `regex` matched, with the named groups of its pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexMatch {
+  text: String,
+  groups: Vec<(String, Option<String>)>,
+}
+
+impl RegexMatch {
+  pub fn as_str(&self) -> &str {
+    &self.text
+  }
+
+  pub fn into_string(self) -> String {
+    self.text
+  }
+
+  // the text of the group `name`, `None` when it took no part in the match
+  pub fn name(&self, name: &str) -> Option<&str> {
+    self
+      .groups
+      .iter()
+      .find(|(group, _)| group == name)
+      .and_then(|(_, text)| text.as_deref())
+  }
+}
+
+// matches `re` at the current position only, the search is anchored there
+// so the pattern needs no `\A` or `^`. Keep `re` in a static so it is
+// compiled once.
+pub fn regex<'a>(re: &'a Regex, expected: &str) -> impl Fn(&Ctx) -> Result<RegexMatch> + 'a {
+  let expected = expected.to_owned();
+  move |ctx| {
+    let success = ctx.parse_regex_captures(re, expected.clone())?;
+    Ok(success.map(|mut groups| {
+      let text = groups[0].take().unwrap_or_default();
+      let groups = re
+        .group_info()
+        .pattern_names(PatternID::ZERO)
+        .zip(groups)
+        .filter_map(|(name, group)| Some((name?.to_owned(), group)))
+        .collect();
+      RegexMatch { text, groups }
+    }))
+  }
+}
+
+// shows up as `name` in the trace of a `Ctx::with_trace` parse, with where it
+// started and how it ended, and does nothing otherwise
+pub fn named<T>(name: &str, parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<T> {
+  let name = name.to_owned();
+  move |ctx| {
+    let Some(tracer) = ctx.tracer() else {
+      return parser(ctx);
+    };
+    tracer.enter(&name, ctx.index());
+    let result = parser(ctx);
+    tracer.exit(match &result {
+      Ok(success) => Outcome::Success(success.span()),
+      Err(failure) => Outcome::Failure {
+        index: failure.index(),
+        expected: failure.expected_set().to_vec(),
+        committed: failure.is_committed(),
+      },
+    });
+    result
+  }
+}
+
+// turns any failure of `parser` into a committed one, use it once the input
+// consumed so far leaves no other valid alternative
+pub fn cut<T>(parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<T> {
+  move |ctx| parser(ctx).map_err(Failure::commit)
+}
+
+// When the ctx is recovering, a committed failure of `parser` is recorded as
+// a diagnostic, `skip` consumes the broken input from where `parser` started
+// and `fallback` is returned instead. Otherwise it is just `parser`.
+pub fn recover<T, S>(
+  parser: impl Fn(&Ctx) -> Result<T>,
+  skip: impl Fn(&Ctx) -> Result<S>,
+  fallback: impl Fn() -> T,
+) -> impl Fn(&Ctx) -> Result<T> {
+  move |ctx| {
+    let failure = match parser(ctx) {
+      Err(failure) if failure.is_committed() && ctx.is_recovering() => failure,
+      res => return res,
+    };
+
+    let skipped = skip(ctx).map_err(|_| failure.clone())?;
+    let next_ctx = failure
+      .ctx()
+      .record(Diagnostic::from(&failure))
+      .next(skipped.index());
+    Ok(next_ctx.success_from(ctx.index(), fallback()))
+  }
+}
+
+// Packrat parsing: the result of `parser` at each position is computed once
+// per parse and then reused, so a PEG grammar with memoized rules runs in
+// linear time however much its alternatives backtrack.
+// A parser without state (a `fn` item, a closure capturing nothing) is keyed
+// by its type, so a recursive rule can call `memo(rule)` each time. Any other
+// parser is keyed by this instance, build it once and reuse it.
+pub fn memo<T, P>(parser: P) -> impl Fn(&Ctx) -> Result<T>
+where
+  T: Clone + 'static,
+  P: Fn(&Ctx) -> Result<T> + 'static,
+{
+  let id = memo_id::<P>();
+  move |ctx| ctx.memoized(id, &parser)
+}
+
+// A memoized rule that may call itself first, `expr = expr "." ident | call`,
+// parsed by growing a seed (Warth et al.): the recursive call at the same
+// position fails at first, and the rule is run again on top of its previous
+// result as long as that gets further. The repetitions are left associative.
+// In an indirect cycle, `a = b "x" | "a"` and `b = a "y"`, wrap one rule of
+// the cycle only and do not `memo` the others.
+pub fn left_rec<T, P>(parser: P) -> impl Fn(&Ctx) -> Result<T>
+where
+  T: Clone + 'static,
+  P: Fn(&Ctx) -> Result<T> + 'static,
+{
+  let id = memo_id::<P>();
+  move |ctx| ctx.grown(id, &parser)
+}
+
+fn memo_id<P: 'static>() -> MemoId {
+  static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+  if std::mem::size_of::<P>() == 0 {
+    MemoId::Type(TypeId::of::<P>())
+  } else {
+    MemoId::Instance(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+  }
+}
+
+pub fn many<T>(parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<Vec<T>> {
   move |ctx| {
     let mut values: Vec<T> = vec![];
     let mut next_ctx = ctx.to_owned();
     loop {
       match parser(&next_ctx) {
+        Err(failure) if failure.is_committed() => return Err(failure),
         Err(_) => break,
         Ok(success) => {
-          next_ctx = success.ctx().to_owned();
-          let val = success.val();
+          let (ctx, val) = success.into_parts();
+          next_ctx = ctx;
           values.push(val);
         }
       }
     }
-    Ok(next_ctx.success(values))
+    Ok(next_ctx.success_from(ctx.index(), values))
   }
 }
 
-pub fn delimited<T: Clone, L, R>(
+pub fn delimited<T, L, R>(
   left: impl Fn(&Ctx) -> Result<L>,
   parser: impl Fn(&Ctx) -> Result<T>,
   right: impl Fn(&Ctx) -> Result<R>,
 ) -> impl Fn(&Ctx) -> Result<T> {
   move |ctx| {
     let l_res = left(ctx)?;
-    let mut next_ctx = l_res.ctx();
-    let res = parser(next_ctx)?;
-    next_ctx = res.ctx();
-    let r_res = right(next_ctx)?;
-    next_ctx = r_res.ctx();
-    Ok(next_ctx.success(res.val()))
+    let next_ctx = l_res.ctx();
+    let (next_ctx, val) = parser(next_ctx)?.into_parts();
+    let r_res = right(&next_ctx)?;
+    Ok(r_res.ctx().success_from(ctx.index(), val))
   }
 }
 
-pub fn delimited_left<T: Clone, L>(
+pub fn delimited_left<T, L>(
   left: impl Fn(&Ctx) -> Result<L>,
   parser: impl Fn(&Ctx) -> Result<T>,
 ) -> impl Fn(&Ctx) -> Result<T> {
   move |ctx| {
     let l_res = left(ctx)?;
     let next_ctx = l_res.ctx();
-    parser(next_ctx)
+    let (next_ctx, val) = parser(next_ctx)?.into_parts();
+    Ok(next_ctx.success_from(ctx.index(), val))
   }
 }
 
-pub fn separated<T: Clone>(
-  separator: impl Fn(&Ctx) -> Result<String>,
+// parser ( separator parser ) *, an empty list when the first parser fails
+pub fn separated<T, S>(
+  separator: impl Fn(&Ctx) -> Result<S>,
   parser: impl Fn(&Ctx) -> Result<T>,
 ) -> impl Fn(&Ctx) -> Result<Vec<T>> {
-  let is_firt = Cell::new(true);
-  let skip_first = move |ctx: &Ctx| {
-    if is_firt.get() {
-      is_firt.set(false);
-      Ok(ctx.success("".to_owned()))
-    } else {
-      separator(ctx)
+  move |ctx| match _separated(ctx, &separator, &parser, false) {
+    Err(failure) if !failure.is_committed() => Ok(ctx.success(vec![])),
+    res => res,
+  }
+}
+
+// parser ( separator parser ) *, at least one element
+pub fn separated1<T, S>(
+  separator: impl Fn(&Ctx) -> Result<S>,
+  parser: impl Fn(&Ctx) -> Result<T>,
+) -> impl Fn(&Ctx) -> Result<Vec<T>> {
+  move |ctx| _separated(ctx, &separator, &parser, false)
+}
+
+// like `separated` but a separator after the last element is consumed too
+pub fn separated_trailing<T, S>(
+  separator: impl Fn(&Ctx) -> Result<S>,
+  parser: impl Fn(&Ctx) -> Result<T>,
+) -> impl Fn(&Ctx) -> Result<Vec<T>> {
+  move |ctx| match _separated(ctx, &separator, &parser, true) {
+    Err(failure) if !failure.is_committed() => Ok(ctx.success(vec![])),
+    res => res,
+  }
+}
+
+fn _separated<T, S>(
+  ctx: &Ctx,
+  separator: &impl Fn(&Ctx) -> Result<S>,
+  parser: &impl Fn(&Ctx) -> Result<T>,
+  trailing: bool,
+) -> Result<Vec<T>> {
+  let (mut next_ctx, first) = parser(ctx)?.into_parts();
+  let mut values = vec![first];
+  loop {
+    let after_separator = match separator(&next_ctx) {
+      Ok(success) => success.into_parts().0,
+      Err(failure) if failure.is_committed() => return Err(failure),
+      Err(_) => break,
+    };
+    match parser(&after_separator) {
+      Ok(success) => {
+        let (ctx, val) = success.into_parts();
+        next_ctx = ctx;
+        values.push(val);
+      }
+      Err(failure) if failure.is_committed() => return Err(failure),
+      Err(_) => {
+        if trailing {
+          next_ctx = after_separator;
+        }
+        break;
+      }
+    }
+  }
+  Ok(next_ctx.success_from(ctx.index(), values))
+}
+
+// How infix operators of the same precedence group, `1 - 2 - 3` is
+// `(1 - 2) - 3` when `Left` and `1 - (2 - 3)` when `Right`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+  Left,
+  Right,
+}
+
+// Where an operator goes and how it builds a value from its operands
+pub enum Fixity<T> {
+  Prefix(Box<dyn Fn(T) -> T>),
+  Infix(Assoc, Box<dyn Fn(T, T) -> T>),
+  Postfix(Box<dyn Fn(T) -> T>),
+}
+
+// A row of the operator table given to `precedence`, operators with a higher
+// precedence bind tighter
+pub struct Operator<T> {
+  token: ParserFn<()>,
+  precedence: u32,
+  fixity: Fixity<T>,
+}
+
+impl<T> Operator<T> {
+  pub fn prefix<S>(
+    token: impl Fn(&Ctx) -> Result<S> + 'static,
+    precedence: u32,
+    build: impl Fn(T) -> T + 'static,
+  ) -> Self {
+    Operator::new(token, precedence, Fixity::Prefix(Box::new(build)))
+  }
+
+  pub fn infix<S>(
+    token: impl Fn(&Ctx) -> Result<S> + 'static,
+    precedence: u32,
+    assoc: Assoc,
+    build: impl Fn(T, T) -> T + 'static,
+  ) -> Self {
+    Operator::new(token, precedence, Fixity::Infix(assoc, Box::new(build)))
+  }
+
+  pub fn postfix<S>(
+    token: impl Fn(&Ctx) -> Result<S> + 'static,
+    precedence: u32,
+    build: impl Fn(T) -> T + 'static,
+  ) -> Self {
+    Operator::new(token, precedence, Fixity::Postfix(Box::new(build)))
+  }
+
+  fn new<S>(
+    token: impl Fn(&Ctx) -> Result<S> + 'static,
+    precedence: u32,
+    fixity: Fixity<T>,
+  ) -> Self {
+    Operator {
+      token: Box::new(move |ctx| Ok(token(ctx)?.map(|_| ()))),
+      precedence,
+      fixity,
+    }
+  }
+
+  pub fn precedence(&self) -> u32 {
+    self.precedence
+  }
+
+  pub fn fixity(&self) -> &Fixity<T> {
+    &self.fixity
+  }
+}
+
+// Precedence climbing over `operand` and the operators in `table`. Operators
+// are tried in table order, so list `<=` before `<`. The operand is tried
+// before any prefix operator, that way a signed literal stays a literal.
+// An operator must be followed by an operand, failures after one are committed.
+pub fn precedence<T>(
+  operand: impl Fn(&Ctx) -> Result<T>,
+  table: Vec<Operator<T>>,
+) -> impl Fn(&Ctx) -> Result<T> {
+  move |ctx| _precedence(ctx, &operand, &table, 0)
+}
+
+fn _precedence<T>(
+  ctx: &Ctx,
+  operand: &impl Fn(&Ctx) -> Result<T>,
+  table: &[Operator<T>],
+  min_precedence: u32,
+) -> Result<T> {
+  let (mut next_ctx, mut lhs) = match operand(ctx) {
+    Ok(success) => success.into_parts(),
+    Err(failure) if failure.is_committed() => return Err(failure),
+    Err(mut failure) => {
+      let mut prefix = None;
+      for op in table {
+        let Fixity::Prefix(build) = &op.fixity else {
+          continue;
+        };
+        match (op.token)(ctx) {
+          Ok(success) => {
+            prefix = Some((success.into_parts().0, op.precedence, build));
+            break;
+          }
+          Err(op_failure) if op_failure.is_committed() => return Err(op_failure),
+          Err(op_failure) => failure = failure.merge(op_failure),
+        }
+      }
+      let (after_op, precedence, build) = prefix.ok_or(failure)?;
+      let (next_ctx, val) = _precedence(&after_op, operand, table, precedence)
+        .map_err(Failure::commit)?
+        .into_parts();
+      (next_ctx, build(val))
     }
   };
 
-  many(delimited_left(skip_first, parser))
-}
-
-//fn map<T: Clone, R>(
-//  parser: impl Fn(&Ctx) -> Result<T>,
-//  op: impl Fn(T) -> R,
-//) -> impl Fn(&Ctx) -> Result<R> {
-//  move |ctx| {
-//    let result = parser(ctx);
-//    match result {
-//      Ok(success) => {
-//        let ctx = success.ctx();
-//        let val = op(success.val());
-//        Ok(ctx.success(val))
-//      }
-//      Err(failure) => Err(failure),
-//    }
-//  }
-//}
-
-//#[cfg(test)]
-//mod test {
-//  use crate::foundation::Ctx;
-//}
+  loop {
+    let mut matched = None;
+    for op in table {
+      if op.precedence < min_precedence || matches!(op.fixity, Fixity::Prefix(_)) {
+        continue;
+      }
+      match (op.token)(&next_ctx) {
+        Ok(success) => {
+          matched = Some((success.into_parts().0, op));
+          break;
+        }
+        Err(failure) if failure.is_committed() => return Err(failure),
+        Err(_) => {}
+      }
+    }
+    let Some((after_op, op)) = matched else {
+      break;
+    };
+
+    match &op.fixity {
+      Fixity::Postfix(build) => {
+        next_ctx = after_op;
+        lhs = build(lhs);
+      }
+      Fixity::Infix(assoc, build) => {
+        let rhs_precedence = match assoc {
+          Assoc::Left => op.precedence + 1,
+          Assoc::Right => op.precedence,
+        };
+        let (ctx, rhs) = _precedence(&after_op, operand, table, rhs_precedence)
+          .map_err(Failure::commit)?
+          .into_parts();
+        next_ctx = ctx;
+        lhs = build(lhs, rhs);
+      }
+      Fixity::Prefix(_) => unreachable!(),
+    }
+  }
+  Ok(next_ctx.success_from(ctx.index(), lhs))
+}
+
+// skips any amount of trivia, only an unterminated block comment fails
+pub fn trivia(config: Trivia) -> impl Fn(&Ctx) -> Result<()> {
+  move |ctx| {
+    let mut next_ctx = ctx.to_owned();
+    loop {
+      let text = next_ctx.text_slice();
+      let len = if config.whitespace && text.starts_with(char::is_whitespace) {
+        text
+          .find(|c: char| !c.is_whitespace())
+          .unwrap_or(text.len())
+      } else if config.line_comments && text.starts_with("//") {
+        text.find('\n').unwrap_or(text.len())
+      } else if config.block_comments && text.starts_with("/*") {
+        match text[2..].find("*/") {
+          Some(end) => end + 4,
+          None => {
+            let end = next_ctx.skip(text.len());
+            return Err(end.failure("*/".to_owned()).commit());
+          }
+        }
+      } else {
+        break;
+      };
+      next_ctx = next_ctx.skip(len);
+    }
+    Ok(next_ctx.success_from(ctx.index(), ()))
+  }
+}
+
+// runs `parser` and skips the trivia after it, the span stays the token's
+pub fn lexeme<T>(config: Trivia, parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<T> {
+  let skip = trivia(config);
+  move |ctx| {
+    let success = parser(ctx)?;
+    let after = skip(success.ctx())?;
+    Ok(success.with_ctx(after.into_parts().0))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::foundation::{Ctx, Span};
+  use std::{cell::Cell, rc::Rc};
+
+  fn digit(ctx: &Ctx) -> Result<u32> {
+    let re = Regex::new(r"^[0-9]").unwrap();
+    let success = ctx.parse_regex(&re, "digit".to_owned())?;
+    let val = success.val().parse().unwrap();
+    Ok(success.map(|_| val))
+  }
+
+  fn letter(ctx: &Ctx) -> Result<String> {
+    let re = Regex::new(r"^[a-z]").unwrap();
+    ctx.parse_regex(&re, "letter".to_owned())
+  }
+
+  fn comma(ctx: &Ctx) -> Result<String> {
+    ctx.parse_str(",")
+  }
+
+  #[test]
+  fn test_map() {
+    let parser = digit.map(|d| d * 10);
+    let success = parser.parse(&Ctx::new("7a")).unwrap();
+    assert_eq!(success.val(), 70);
+    assert_eq!(success.index(), 1);
+  }
+
+  #[test]
+  fn test_and_then() {
+    // the first digit tells how many letters follow
+    let parser = digit.and_then(|n| {
+      move |ctx: &Ctx| {
+        let mut next_ctx = ctx.to_owned();
+        let mut word = String::new();
+        for _ in 0..n {
+          let (ctx, val) = letter(&next_ctx)?.into_parts();
+          next_ctx = ctx;
+          word.push_str(&val);
+        }
+        Ok(next_ctx.success(word))
+      }
+    });
+
+    let success = parser.parse(&Ctx::new("3abcd")).unwrap();
+    assert_eq!(success.val(), "abc");
+    assert_eq!(success.index(), 4);
+
+    let failure = parser.parse(&Ctx::new("3ab")).unwrap_err();
+    assert_eq!(failure.expected(), "letter");
+    assert_eq!(failure.index(), 3);
+  }
+
+  #[test]
+  fn test_or() {
+    let parser = digit.map(|d| d.to_string()).or(letter);
+
+    assert_eq!(parser.parse(&Ctx::new("1")).unwrap().val(), "1");
+    assert_eq!(parser.parse(&Ctx::new("a")).unwrap().val(), "a");
+
+    let failure = parser.parse(&Ctx::new("-")).unwrap_err();
+    assert_eq!(failure.expected(), "one of: digit, letter");
+    assert_eq!(failure.index(), 0);
+
+    // the failure that got further wins
+    let parser = digit
+      .then(letter)
+      .map(|_| ())
+      .or(digit.map(|_| ()).then(digit).map(|_| ()));
+    let failure = parser.parse(&Ctx::new("1-")).unwrap_err();
+    assert_eq!(failure.index(), 1);
+
+    // unless the second one is committed, as in `any`
+    let first = || letter.then(digit).map(|_| ());
+    let second = || cut(letter.map(|_| ()).then(letter).map(|_| ()));
+    let failure = first().or(second()).parse(&Ctx::new("x-")).unwrap_err();
+    assert!(failure.is_committed());
+    let parsers: Vec<ParserFn<()>> = vec![Box::new(first()), Box::new(second())];
+    assert_eq!(any(parsers)(&Ctx::new("x-")), Err(failure));
+  }
+
+  #[test]
+  fn test_any() {
+    let parser = any(vec![
+      Box::new(digit.map(|d| d.to_string())),
+      Box::new(letter),
+      Box::new(comma),
+    ]);
+    assert_eq!(parser(&Ctx::new(",")).unwrap().val(), ",");
+
+    let failure = parser(&Ctx::new("-")).unwrap_err();
+    assert_eq!(failure.expected_set(), ["digit", "letter", ","]);
+    assert_eq!(failure.expected(), "one of: digit, letter, ,");
+
+    // alternatives that failed earlier are dropped
+    let parser = any(vec![
+      Box::new(digit.skip(letter).map(|d| d.to_string())),
+      Box::new(digit.skip(comma).map(|d| d.to_string())),
+      Box::new(letter),
+    ]);
+    let failure = parser(&Ctx::new("1-")).unwrap_err();
+    assert_eq!(failure.expected_set(), ["letter", ","]);
+    assert_eq!(failure.index(), 1);
+
+    let failure = any::<()>(vec![])(&Ctx::new("")).unwrap_err();
+    assert_eq!(failure.expected(), "any");
+  }
+
+  #[test]
+  fn test_cut() {
+    let failure = cut(digit)(&Ctx::new("a")).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), "digit");
+
+    // `any` stops at the first committed failure
+    let parser = any(vec![
+      Box::new(comma.then(cut(digit)).map(|(_, d)| d.to_string())),
+      Box::new(comma),
+    ]);
+    assert_eq!(parser(&Ctx::new(",1")).unwrap().val(), "1");
+    let failure = parser(&Ctx::new(",a")).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), "digit");
+    assert_eq!(failure.index(), 1);
+
+    // and so do `or` and `optional`
+    let parser = comma.then(digit.cut()).map(|_| ()).or(comma.map(|_| ()));
+    assert!(parser(&Ctx::new(",a")).unwrap_err().is_committed());
+    let parser = comma.then(digit.cut()).optional();
+    assert!(parser(&Ctx::new(",a")).unwrap_err().is_committed());
+    assert_eq!(parser(&Ctx::new("a")).unwrap().val(), None);
+  }
+
+  #[test]
+  fn test_many_committed() {
+    let parser = many(comma.then(cut(digit)));
+    let success = parser(&Ctx::new(",1,2;")).unwrap();
+    assert_eq!(success.val().len(), 2);
+    assert_eq!(success.index(), 4);
+
+    let failure = parser(&Ctx::new(",1,a")).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.index(), 3);
+
+    let parser = separated(comma, cut(digit));
+    let failure = parser(&Ctx::new("1,a")).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.index(), 2);
+  }
+
+  #[test]
+  fn test_recover() {
+    let skip = |ctx: &Ctx| ctx.parse_regex(&Regex::new("^[^,]*").unwrap(), "".to_owned());
+    let element = |ctx: &Ctx| recover(cut(digit), skip, || 0)(ctx);
+
+    // does nothing unless the ctx is recovering
+    let failure = separated(comma, element)(&Ctx::new("1,a,3")).unwrap_err();
+    assert!(failure.is_committed());
+
+    let success = separated(comma, element)(&Ctx::new("1,ab,3,").with_recovery()).unwrap();
+    assert_eq!(success.val(), vec![1, 0, 3, 0]);
+    assert_eq!(success.index(), 7);
+
+    let diagnostics = success.ctx().diagnostics();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].span(), Span::new(2, 4));
+    assert_eq!(diagnostics[0].expected(), ["digit"]);
+    assert_eq!(diagnostics[1].message(), "unexpected end of input");
+
+    // recoverable failures are left to the other combinators
+    let failure = recover(digit, skip, || 0)(&Ctx::new("a").with_recovery()).unwrap_err();
+    assert!(!failure.is_committed());
+  }
+
+  thread_local! {
+    static RUNS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+  }
+
+  // a = "(" a ")" "x" | "(" a ")" "y" | "z"
+  // on "((z)y)y" every level parses `a` twice, the first alternative fails on
+  // the "y" only after the nested `a` succeeded
+  fn nested(ctx: &Ctx, a: fn(&Ctx) -> Result<usize>) -> Result<usize> {
+    RUNS.with(|runs| runs.set(runs.get() + 1));
+    let token = |token: &'static str| move |ctx: &Ctx| ctx.parse_str(token);
+    let group = |end| delimited(token("("), a, token(")")).skip(token(end));
+    let leaf = token("z").map(|_| 0);
+    group("x")
+      .or(group("y"))
+      .map(|depth| depth + 1)
+      .or(leaf)
+      .parse(ctx)
+  }
+
+  fn plain(ctx: &Ctx) -> Result<usize> {
+    nested(ctx, plain)
+  }
+
+  fn memoized(ctx: &Ctx) -> Result<usize> {
+    memo(|ctx: &Ctx| nested(ctx, memoized))(ctx)
+  }
+
+  #[test]
+  fn test_memo() {
+    let code = format!("{}z{}", "(".repeat(12), ")y".repeat(12));
+    let runs = |parser: fn(&Ctx) -> Result<usize>| {
+      RUNS.with(|runs| runs.set(0));
+      let success = parser(&Ctx::new(&code)).unwrap();
+      assert_eq!(success.val(), 12);
+      assert_eq!(success.index(), code.len());
+      RUNS.with(|runs| runs.get())
+    };
+    assert_eq!(runs(plain), 2usize.pow(13) - 1);
+    // once per level, and the table does not outlive the parse
+    assert_eq!(runs(memoized), 13);
+    assert_eq!(runs(memoized), 13);
+
+    let failure = memoized(&Ctx::new("((z)y)w")).unwrap_err();
+    assert_eq!(failure, plain(&Ctx::new("((z)y)w")).unwrap_err());
+
+    // parsers with state are told apart by instance
+    let word = |word: &'static str| memo(move |ctx: &Ctx| ctx.parse_str(word));
+    let parser = word("ab").or(word("a"));
+    assert_eq!(parser(&Ctx::new("a")).unwrap().val(), "a");
+  }
+
+  #[test]
+  fn test_memo_recovery() {
+    let skip = |ctx: &Ctx| ctx.parse_regex(&Regex::new("^[^,]*").unwrap(), "".to_owned());
+    let element = memo(move |ctx: &Ctx| recover(cut(digit), skip, || 0)(ctx));
+
+    let ctx = Ctx::new("a,").with_recovery();
+    let success = element(&ctx).unwrap();
+    assert_eq!(success.ctx().diagnostics().len(), 1);
+
+    // the same position reached with other diagnostics is parsed again
+    let earlier = Diagnostic::from(&digit(&Ctx::new("-")).unwrap_err());
+    let success = element(&ctx.record(earlier.clone())).unwrap();
+    let diagnostics = success.ctx().diagnostics();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0], earlier);
+  }
+
+  #[test]
+  fn test_memo_modes() {
+    let runs = Rc::new(Cell::new(0));
+    let counted = {
+      let runs = Rc::clone(&runs);
+      move |ctx: &Ctx| {
+        runs.set(runs.get() + 1);
+        cut(digit)(ctx)
+      }
+    };
+    let skip = |ctx: &Ctx| ctx.parse_regex(&Regex::new("[^,]*").unwrap(), "".to_owned());
+    let element = memo(recover(named("digit", counted), skip, || 0));
+
+    // the failure memoized without recovery is not replayed with it
+    let ctx = Ctx::new("a,");
+    assert!(element(&ctx).is_err());
+    let ctx = ctx.with_recovery();
+    let success = element(&ctx).unwrap();
+    assert_eq!(success.ctx().diagnostics().len(), 1);
+    assert_eq!(runs.get(), 2);
+
+    // nor is a result parsed untraced when tracing
+    let ctx = ctx.with_trace();
+    element(&ctx).unwrap();
+    assert_eq!(runs.get(), 3);
+    assert_eq!(ctx.trace().unwrap().roots()[0].name, "digit");
+  }
+
+  // chain = chain "." letter | chain "(" ")" | letter
+  fn chain(ctx: &Ctx) -> Result<String> {
+    left_rec(|ctx: &Ctx| {
+      let token = |token: &'static str| move |ctx: &Ctx| ctx.parse_str(token);
+      let member = chain
+        .skip(token("."))
+        .then(cut(letter))
+        .map(|(obj, name)| format!("({obj}.{name})"));
+      let call = chain.skip(token("(")).skip(token(")")).map(|f| f + "()");
+      member.or(call).or(letter).parse(ctx)
+    })(ctx)
+  }
+
+  #[test]
+  fn test_left_rec() {
+    let success = chain(&Ctx::new("a.b().c")).unwrap();
+    assert_eq!(success.val(), "((a.b)().c)");
+    assert_eq!(success.index(), 7);
+    assert_eq!(success.span(), Span::new(0, 7));
+
+    // stops at the longest match
+    let success = chain(&Ctx::new("a()(x")).unwrap();
+    assert_eq!(success.val(), "a()");
+    assert_eq!(success.index(), 3);
+
+    // the seed adds nothing to what is expected
+    let failure = chain(&Ctx::new("1")).unwrap_err();
+    assert_eq!(failure.expected(), "letter");
+    assert_eq!(failure.index(), 0);
+
+    let failure = chain(&Ctx::new("a.b.1")).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.index(), 4);
+
+    // sub = sub "-" digit | digit, left associative
+    fn sub(ctx: &Ctx) -> Result<u32> {
+      let minus = |ctx: &Ctx| ctx.parse_str("-");
+      left_rec(move |ctx: &Ctx| {
+        sub
+          .skip(minus)
+          .then(digit)
+          .map(|(lhs, rhs)| lhs.wrapping_sub(rhs))
+          .or(digit)
+          .parse(ctx)
+      })(ctx)
+    }
+    assert_eq!(sub(&Ctx::new("9-2-1")).unwrap().val(), 6);
+    assert_eq!(sub(&Ctx::new("9-2-1-")).unwrap().index(), 5);
+  }
+
+  #[test]
+  fn test_left_rec_indirect() {
+    // a = b "x" | "a", b = a "y"
+    fn a(ctx: &Ctx) -> Result<String> {
+      let x = |ctx: &Ctx| ctx.parse_str("x");
+      let leaf = |ctx: &Ctx| ctx.parse_str("a");
+      left_rec(move |ctx: &Ctx| b.then(x).map(|(b, x)| b + &x).or(leaf).parse(ctx))(ctx)
+    }
+
+    fn b(ctx: &Ctx) -> Result<String> {
+      let y = |ctx: &Ctx| ctx.parse_str("y");
+      a.then(y).map(|(a, y)| a + &y).parse(ctx)
+    }
+
+    let success = a(&Ctx::new("ayxyxy")).unwrap();
+    assert_eq!(success.val(), "ayxyx");
+    assert_eq!(success.index(), 5);
+
+    let success = b(&Ctx::new("ayxy")).unwrap();
+    assert_eq!(success.val(), "ayxy");
+  }
+
+  #[test]
+  fn test_chars() {
+    let ctx = Ctx::new("ñ€");
+    let success = any_char(&ctx).unwrap();
+    assert_eq!(success.val(), 'ñ');
+    assert_eq!(success.span(), Span::new(0, 2));
+    let success = any_char(success.ctx()).unwrap();
+    assert_eq!(success.val(), '€');
+    assert_eq!(success.index(), 5);
+    let failure = any_char(success.ctx()).unwrap_err();
+    assert_eq!(failure.expected(), "any character");
+
+    let upper = satisfy(char::is_uppercase, "uppercase letter");
+    assert_eq!(upper(&Ctx::new("Émile")).unwrap().val(), 'É');
+    assert_eq!(
+      upper(&Ctx::new("émile")).unwrap_err().expected(),
+      "uppercase letter"
+    );
+
+    let hex = char_class("0-9a-fA-F");
+    assert_eq!(many(&hex)(&Ctx::new("c0ffee!")).unwrap().index(), 6);
+    let failure = hex(&Ctx::new("g")).unwrap_err();
+    assert_eq!(failure.expected(), "[0-9a-fA-F]");
+
+    let sign = char_class("+-");
+    assert_eq!(sign(&Ctx::new("-")).unwrap().val(), '-');
+    assert!(sign(&Ctx::new("a")).is_err());
+
+    let not_quote = char_class("^\"\\");
+    assert_eq!(not_quote(&Ctx::new("ñ")).unwrap().val(), 'ñ');
+    assert!(not_quote(&Ctx::new("\"")).is_err());
+    assert!(not_quote(&Ctx::new("")).is_err());
+  }
+
+  #[test]
+  fn test_one_of_str() {
+    let op = one_of_str(&["<", "<=", "<<", "<<=", "=", "=="]);
+    let success = op(&Ctx::new("<<= 1")).unwrap();
+    assert_eq!(success.val(), "<<=");
+    assert_eq!(success.span(), Span::new(0, 3));
+    assert_eq!(op(&Ctx::new("<= 1")).unwrap().val(), "<=");
+    assert_eq!(op(&Ctx::new("<< 1")).unwrap().val(), "<<");
+    // a longer prefix that is not a word falls back to the last word seen
+    assert_eq!(op(&Ctx::new("<<> 1")).unwrap().val(), "<<");
+    assert_eq!(op(&Ctx::new("===")).unwrap().val(), "==");
+
+    let failure = op(&Ctx::new("> 1")).unwrap_err();
+    assert_eq!(failure.index(), 0);
+    assert_eq!(failure.expected(), "one of: <, <=, <<, <<=, =, ==");
+
+    let word = one_of_str(&["ñu", "ñandú"]);
+    assert_eq!(word(&Ctx::new("ñandúes")).unwrap().val(), "ñandú");
+    assert!(word(&Ctx::new("ña")).is_err());
+  }
+
+  #[test]
+  fn test_named() {
+    let word = |w: &'static str| named(w, move |ctx: &Ctx| ctx.parse_str(w));
+    let parser = named(
+      "choice",
+      any(vec![Box::new(word("null")), Box::new(word("nil"))]),
+    );
+
+    // nothing is recorded without `with_trace`
+    let ctx = Ctx::new("nil");
+    assert_eq!(parser(&ctx).unwrap().val(), "nil");
+    assert_eq!(ctx.trace(), None);
+
+    let ctx = Ctx::new("nil").with_trace();
+    parser(&ctx).unwrap();
+    let trace = ctx.trace().unwrap();
+    assert_eq!(
+      trace.to_text(),
+      "choice 0..3 ok \"nil\"\n  null 0..0 backtrack, expected null\n  nil 0..3 ok \"nil\"\n"
+    );
+
+    // the method form, a failure of the whole parse is traced too
+    let ctx = Ctx::new("no").with_trace();
+    word("null").named("keyword")(&ctx).unwrap_err();
+    let trace = ctx.trace().unwrap();
+    let root = &trace.roots()[0];
+    assert_eq!(root.name, "keyword");
+    assert_eq!(root.children[0].name, "null");
+    assert!(!root.is_success());
+  }
+
+  #[test]
+  fn test_regex() {
+    let re = Regex::new(r"(?P<name>[a-z]+)(=(?P<value>[0-9]+))?").unwrap();
+    let parser = regex(&re, "setting");
+
+    let success = parser(&Ctx::new("width=80;")).unwrap();
+    assert_eq!(success.val().as_str(), "width=80");
+    assert_eq!(success.val().name("name"), Some("width"));
+    assert_eq!(success.val().name("value"), Some("80"));
+    assert_eq!(success.val().name("other"), None);
+    assert_eq!(success.span(), Span::new(0, 8));
+
+    let success = parser(&Ctx::new("wrap;")).unwrap();
+    assert_eq!(success.val().name("value"), None);
+
+    // only at the current position
+    let failure = parser(&Ctx::new("80 width")).unwrap_err();
+    assert_eq!(failure.expected(), "setting");
+    assert_eq!(failure.index(), 0);
+    let success = parser(&Ctx::new("80 width").skip(3)).unwrap();
+    assert_eq!(success.val().into_string(), "width");
+
+    // an alternative does not escape the anchor
+    let re = Regex::new("a|b").unwrap();
+    assert!(regex(&re, "a or b")(&Ctx::new("cb")).is_err());
+
+    // the only number is at the end of 1MB: it is not taken from any earlier
+    // position, and none of the 100k misses looks through the rest of the text
+    let text = "x".repeat(1_000_000) + "=42";
+    let ctx = Ctx::new(&text);
+    let re = Regex::new(r"=(?P<value>[0-9]+)").unwrap();
+    let parser = regex(&re, "value");
+    for index in (0..1_000_000).step_by(10) {
+      assert_eq!(parser(&ctx.skip(index)).unwrap_err().index(), index);
+    }
+    let success = parser(&ctx.skip(1_000_000)).unwrap();
+    assert_eq!(success.val().name("value"), Some("42"));
+
+    // the builder options are kept
+    let re = Regex::builder()
+      .syntax(regex_automata::util::syntax::Config::new().case_insensitive(true))
+      .build(r"(?P<word>abc)")
+      .unwrap();
+    let success = regex(&re, "abc")(&Ctx::new("ABC")).unwrap();
+    assert_eq!(success.val().name("word"), Some("ABC"));
+  }
+
+  #[test]
+  fn test_separated_reusable() {
+    let parser = separated(comma, digit);
+    for _ in 0..3 {
+      let success = parser(&Ctx::new("1,2,3")).unwrap();
+      assert_eq!(success.val(), vec![1, 2, 3]);
+      assert_eq!(success.index(), 5);
+      assert_eq!(success.span(), Span::new(0, 5));
+
+      let success = parser(&Ctx::new("4")).unwrap();
+      assert_eq!(success.val(), vec![4]);
+
+      let success = parser(&Ctx::new(",1")).unwrap();
+      assert_eq!(success.val(), Vec::<u32>::new());
+      assert_eq!(success.index(), 0);
+
+      // a separator without an element after it is not consumed
+      let success = parser(&Ctx::new("1,2,")).unwrap();
+      assert_eq!(success.val(), vec![1, 2]);
+      assert_eq!(success.index(), 3);
+    }
+  }
+
+  #[test]
+  fn test_separated1() {
+    let parser = separated1(comma, digit);
+    for _ in 0..3 {
+      let success = parser(&Ctx::new("1,2;")).unwrap();
+      assert_eq!(success.val(), vec![1, 2]);
+      assert_eq!(success.index(), 3);
+
+      let failure = parser(&Ctx::new("a")).unwrap_err();
+      assert_eq!(failure.expected(), "digit");
+      assert_eq!(failure.index(), 0);
+    }
+  }
+
+  #[test]
+  fn test_separated_trailing() {
+    let parser = separated_trailing(comma, digit);
+    for _ in 0..3 {
+      let success = parser(&Ctx::new("1,2,")).unwrap();
+      assert_eq!(success.val(), vec![1, 2]);
+      assert_eq!(success.index(), 4);
+
+      let success = parser(&Ctx::new("1,2")).unwrap();
+      assert_eq!(success.val(), vec![1, 2]);
+      assert_eq!(success.index(), 3);
+
+      // only one trailing separator
+      let success = parser(&Ctx::new("1,,")).unwrap();
+      assert_eq!(success.val(), vec![1]);
+      assert_eq!(success.index(), 2);
+
+      let success = parser(&Ctx::new(",")).unwrap();
+      assert_eq!(success.val(), Vec::<u32>::new());
+      assert_eq!(success.index(), 0);
+    }
+  }
+
+  #[test]
+  fn test_sequence() {
+    let parser = sequence(vec![
+      Box::new(digit.map(|d| d.to_string())),
+      Box::new(letter),
+      Box::new(comma),
+    ]);
+    let success = parser(&Ctx::new("1a,")).unwrap();
+    assert_eq!(success.val(), vec!["1", "a", ","]);
+    assert_eq!(success.span(), Span::new(0, 3));
+
+    let failure = parser(&Ctx::new("1a;")).unwrap_err();
+    assert_eq!(failure.expected(), ",");
+    assert_eq!(failure.index(), 2);
+  }
+
+  #[test]
+  fn test_tuple() {
+    let parser = tuple((digit, letter));
+    let success = parser(&Ctx::new("1a")).unwrap();
+    assert_eq!(success.val(), (1, "a".to_owned()));
+    assert_eq!(success.span(), Span::new(0, 2));
+
+    let parser = tuple((digit, comma, letter, comma, digit));
+    let success = parser(&Ctx::new("1,a,2")).unwrap();
+    assert_eq!(
+      success.val(),
+      (1, ",".to_owned(), "a".to_owned(), ",".to_owned(), 2)
+    );
+
+    let parser = tuple((
+      digit, digit, digit, digit, digit, digit, digit, digit, digit, digit, digit, letter,
+    ));
+    let success = parser(&Ctx::new("12345678901a")).unwrap();
+    assert_eq!(success.val().0, 1);
+    assert_eq!(success.val().11, "a");
+    assert_eq!(success.index(), 12);
+
+    let failure = parser(&Ctx::new("1234567890ab")).unwrap_err();
+    assert_eq!(failure.expected(), "digit");
+    assert_eq!(failure.index(), 10);
+  }
+
+  #[test]
+  fn test_map_and_map_res() {
+    let success = map(digit, |d| d + 1)(&Ctx::new("1")).unwrap();
+    assert_eq!(success.val(), 2);
+
+    let small = map_res(digit.many(), |digits| {
+      let n = digits.iter().fold(0, |acc, d| acc * 10 + d);
+      if n < 100 {
+        Ok(n)
+      } else {
+        Err("a number below 100")
+      }
+    });
+    let success = small(&Ctx::new("42")).unwrap();
+    assert_eq!(success.val(), 42);
+    assert_eq!(success.span(), Span::new(0, 2));
+
+    let failure = small(&Ctx::new("123")).unwrap_err();
+    assert_eq!(failure.expected(), "a number below 100");
+    assert_eq!(failure.index(), 0);
+  }
+
+  #[test]
+  fn test_value_and_opt() {
+    let success = value(true, comma)(&Ctx::new(",")).unwrap();
+    assert!(success.val());
+    assert_eq!(success.index(), 1);
+
+    assert_eq!(opt(digit)(&Ctx::new("1")).unwrap().val(), Some(1));
+    let success = opt(digit)(&Ctx::new("a")).unwrap();
+    assert_eq!(success.val(), None);
+    assert_eq!(success.index(), 0);
+    assert!(opt(cut(digit))(&Ctx::new("a")).is_err());
+  }
+
+  #[test]
+  fn test_not_and_peek() {
+    let success = not(digit)(&Ctx::new("a")).unwrap();
+    assert_eq!(success.index(), 0);
+
+    let failure = not(digit)(&Ctx::new("1")).unwrap_err();
+    assert_eq!(failure.expected(), "anything but `1`");
+    assert_eq!(failure.index(), 0);
+
+    let success = peek(digit)(&Ctx::new("1")).unwrap();
+    assert_eq!(success.val(), 1);
+    assert_eq!(success.index(), 0);
+    assert!(peek(digit)(&Ctx::new("a")).is_err());
+  }
+
+  #[test]
+  fn test_eof_and_recognize() {
+    assert!(eof(&Ctx::new("")).is_ok());
+    assert!(eof(&Ctx::new("1").skip(1)).is_ok());
+    let failure = eof(&Ctx::new("1")).unwrap_err();
+    assert_eq!(failure.expected(), "end of input");
+
+    let parser = recognize(digit.sep_by(comma));
+    let success = parser(&Ctx::new("1,2,3;")).unwrap();
+    assert_eq!(success.val(), "1,2,3");
+    assert_eq!(success.span(), Span::new(0, 5));
+  }
+
+  #[test]
+  fn test_trivia() {
+    let skip = trivia(Trivia::ALL);
+    let ctx = Ctx::new(" \n\t// comment\n /* block\n comment */ 1 // end");
+    let success = skip(&ctx).unwrap();
+    assert_eq!(success.ctx().text_slice(), "1 // end");
+
+    let success = skip(&success.ctx().skip(1)).unwrap();
+    assert_eq!(success.ctx().text_slice(), "");
+
+    let failure = skip(&Ctx::new(" /* open")).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), "*/");
+    assert_eq!(failure.index(), 8);
+
+    // only what is enabled is skipped
+    let whitespace = Trivia {
+      whitespace: true,
+      ..Trivia::NONE
+    };
+    let success = trivia(whitespace)(&Ctx::new("  // comment")).unwrap();
+    assert_eq!(success.ctx().text_slice(), "// comment");
+    let success = trivia(Trivia::NONE)(&Ctx::new("  1")).unwrap();
+    assert_eq!(success.index(), 0);
+  }
+
+  #[test]
+  fn test_lexeme() {
+    let parser = lexeme(Trivia::ALL, digit).sep_by(lexeme(Trivia::ALL, comma));
+    let success = parser(&Ctx::new("1 , 2/**/,3 // end")).unwrap();
+    assert_eq!(success.val(), vec![1, 2, 3]);
+    assert_eq!(success.ctx().text_slice(), "");
+
+    let success = lexeme(Trivia::ALL, digit)(&Ctx::new("1  ")).unwrap();
+    assert_eq!(success.span(), Span::new(0, 1));
+    assert_eq!(success.index(), 3);
+  }
+
+  #[test]
+  fn test_then_and_skip() {
+    let parser = digit.then(letter);
+    let success = parser.parse(&Ctx::new("1a")).unwrap();
+    assert_eq!(success.val(), (1, "a".to_owned()));
+    assert_eq!(success.index(), 2);
+    assert_eq!(success.span(), Span::new(0, 2));
+
+    let parser = digit.skip(comma);
+    let success = parser.parse(&Ctx::new("1,")).unwrap();
+    assert_eq!(success.val(), 1);
+    assert_eq!(success.index(), 2);
+
+    let failure = parser.parse(&Ctx::new("1;")).unwrap_err();
+    assert_eq!(failure.expected(), ",");
+    assert_eq!(failure.index(), 1);
+  }
+
+  #[test]
+  fn test_optional() {
+    let parser = digit.optional();
+
+    let success = parser.parse(&Ctx::new("1")).unwrap();
+    assert_eq!(success.val(), Some(1));
+    assert_eq!(success.index(), 1);
+
+    let success = parser.parse(&Ctx::new("a")).unwrap();
+    assert_eq!(success.val(), None);
+    assert_eq!(success.index(), 0);
+  }
+
+  #[test]
+  fn test_many_and_sep_by() {
+    let success = digit.many().parse(&Ctx::new("123a")).unwrap();
+    assert_eq!(success.val(), vec![1, 2, 3]);
+    assert_eq!(success.index(), 3);
+
+    let success = digit.sep_by(comma).parse(&Ctx::new("1,2,3")).unwrap();
+    assert_eq!(success.val(), vec![1, 2, 3]);
+    assert_eq!(success.index(), 5);
+
+    let success = digit.sep_by1(comma).parse(&Ctx::new("1,2,")).unwrap();
+    assert_eq!(success.val(), vec![1, 2]);
+    assert_eq!(success.index(), 3);
+    assert!(digit.sep_by1(comma).parse(&Ctx::new("")).is_err());
+
+    let success = digit
+      .sep_by_trailing(comma)
+      .parse(&Ctx::new("1,2,"))
+      .unwrap();
+    assert_eq!(success.val(), vec![1, 2]);
+    assert_eq!(success.index(), 4);
+  }
+
+  #[test]
+  fn test_precedence() {
+    let op = |s: &'static str| move |ctx: &Ctx| ctx.parse_str(s);
+    let table: Vec<Operator<i64>> = vec![
+      Operator::infix(op("+"), 1, Assoc::Left, |a, b| a + b),
+      Operator::infix(op("-"), 1, Assoc::Left, |a, b| a - b),
+      Operator::infix(op("*"), 2, Assoc::Left, |a, b| a * b),
+      Operator::infix(op("^"), 4, Assoc::Right, |a: i64, b| a.pow(b as u32)),
+      Operator::prefix(op("-"), 3, |a: i64| -a),
+      Operator::postfix(op("!"), 5, |a: i64| (1..=a).product()),
+    ];
+    let parser = precedence(digit.map(i64::from), table);
+    let eval = |code| parser(&Ctx::new(code)).map(|s| s.val());
+
+    assert_eq!(eval("7"), Ok(7));
+    assert_eq!(eval("1+2*3"), Ok(7));
+    assert_eq!(eval("2*3+1"), Ok(7));
+    assert_eq!(eval("9-3-2"), Ok(4));
+    assert_eq!(eval("2^3^2"), Ok(512));
+    assert_eq!(eval("-2^2"), Ok(-4));
+    assert_eq!(eval("--3"), Ok(3));
+    assert_eq!(eval("3!*2"), Ok(12));
+    assert_eq!(eval("-3!"), Ok(-6));
+
+    let success = parser(&Ctx::new("1+2;")).unwrap();
+    assert_eq!(success.span(), Span::new(0, 3));
+
+    let failure = parser(&Ctx::new("a")).unwrap_err();
+    assert_eq!(failure.expected(), "one of: digit, -");
+    assert!(!failure.is_committed());
+
+    // an operator without its operand
+    let failure = parser(&Ctx::new("1+*")).unwrap_err();
+    assert_eq!(failure.index(), 2);
+    assert!(failure.is_committed());
+  }
+
+  #[test]
+  fn test_label() {
+    let parser = digit.or(digit).label("number");
+    let failure = parser.parse(&Ctx::new("a")).unwrap_err();
+    assert_eq!(failure.expected(), "number");
+    assert_eq!(failure.index(), 0);
+
+    // failures after consuming input keep their own message
+    let parser = digit.skip(comma).label("number");
+    let failure = parser.parse(&Ctx::new("1a")).unwrap_err();
+    assert_eq!(failure.expected(), ",");
+    assert_eq!(failure.index(), 1);
+  }
+}
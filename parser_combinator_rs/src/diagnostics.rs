@@ -0,0 +1,222 @@
+use crate::foundation::{line_col, Failure, Span};
+
+// A parse error detached from the parser that produced it, so it can be
+// rendered against the original source by any front end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+  message: String,
+  span: Span,
+  expected: Vec<String>,
+}
+
+impl Diagnostic {
+  pub fn new(message: impl Into<String>, span: Span) -> Diagnostic {
+    Diagnostic {
+      message: message.into(),
+      span,
+      expected: vec![],
+    }
+  }
+
+  // "unexpected `<token>`" pointing at `span` of `source`, see `snap` for a
+  // span that does not fit it
+  pub fn unexpected(source: &str, span: Span) -> Diagnostic {
+    let span = snap(source, span);
+    let found = &source[span.start..span.end];
+    let message = if found.is_empty() {
+      "unexpected end of input".to_owned()
+    } else {
+      format!("unexpected `{found}`")
+    };
+    Diagnostic::new(message, span)
+  }
+
+  pub fn with_expected(mut self, expected: impl IntoIterator<Item = String>) -> Diagnostic {
+    self.expected = expected.into_iter().collect();
+    self
+  }
+
+  pub fn message(&self) -> &str {
+    &self.message
+  }
+
+  pub fn span(&self) -> Span {
+    self.span
+  }
+
+  pub fn expected(&self) -> &[String] {
+    &self.expected
+  }
+
+  // renders a report like
+  //
+  // error: unexpected `;`
+  //  --> 2:5
+  //   |
+  // 2 |   1;
+  //   |    ^ expected ,
+  pub fn render(&self, source: &str, color: bool) -> String {
+    let paint = |code: &str, text: &str| {
+      if color {
+        format!("\x1b[{code}m{text}\x1b[0m")
+      } else {
+        text.to_owned()
+      }
+    };
+
+    let span = snap(source, self.span);
+    let start = span.start;
+    let (line, column) = line_col(source, start);
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+      .find('\n')
+      .map_or(source.len(), |i| start + i);
+    let line_text = &source[line_start..line_end];
+
+    // the underline never goes past the end of the line and is at least one char wide
+    let end = span.end.min(line_end);
+    let offset = source[line_start..start].chars().count();
+    let width = source[start..end].chars().count().max(1);
+
+    let gutter = line.to_string();
+    let pad = " ".repeat(gutter.len());
+    let bar = paint("1;34", "|");
+
+    let mut report = String::new();
+    report.push_str(&format!(
+      "{}: {}\n",
+      paint("1;31", "error"),
+      paint("1", &self.message)
+    ));
+    report.push_str(&format!("{pad}{} {line}:{column}\n", paint("1;34", "-->")));
+    report.push_str(&format!("{pad} {bar}\n"));
+    report.push_str(&format!("{} {bar} {line_text}\n", paint("1;34", &gutter)));
+    report.push_str(&format!(
+      "{pad} {bar} {}{}",
+      " ".repeat(offset),
+      paint("1;31", &"^".repeat(width))
+    ));
+    match self.expected.as_slice() {
+      [] => {}
+      [one] => report.push_str(&format!(" expected {one}")),
+      many => report.push_str(&format!(" expected one of: {}", many.join(", "))),
+    }
+    report.push('\n');
+    report
+  }
+}
+
+// `span` clamped to `source` and widened to whole chars, a start inside a
+// multi-byte char moves back to it and an end moves past it
+fn snap(source: &str, span: Span) -> Span {
+  let mut start = span.start.min(source.len());
+  while !source.is_char_boundary(start) {
+    start -= 1;
+  }
+  let mut end = span.end.clamp(start, source.len());
+  while !source.is_char_boundary(end) {
+    end += 1;
+  }
+  Span::new(start, end)
+}
+
+impl From<&Failure> for Diagnostic {
+  fn from(failure: &Failure) -> Self {
+    Diagnostic::unexpected(failure.ctx().text(), failure.span())
+      .with_expected(failure.expected_set().to_vec())
+  }
+}
+
+impl From<Failure> for Diagnostic {
+  fn from(failure: Failure) -> Self {
+    Diagnostic::from(&failure)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::Diagnostic;
+  use crate::foundation::{Ctx, Span};
+
+  #[test]
+  fn test_render_failure() {
+    let source = "Foo(\n  1;\n)";
+    let failure = Ctx::new(source).skip(8).parse_str(",").unwrap_err();
+    let diagnostic = Diagnostic::from(&failure);
+    assert_eq!(diagnostic.message(), "unexpected `;`");
+    assert_eq!(diagnostic.span(), Span::new(8, 9));
+
+    let expected = "\
+error: unexpected `;`
+ --> 2:4
+  |
+2 |   1;
+  |    ^ expected ,
+";
+    assert_eq!(diagnostic.render(source, false), expected);
+  }
+
+  #[test]
+  fn test_render_word_and_alternatives() {
+    let source = "Foo(bar)";
+    let diagnostic = Diagnostic::new("unexpected `bar`", Span::new(4, 7))
+      .with_expected(["number".to_string(), "boolean".to_string()]);
+
+    let expected = "\
+error: unexpected `bar`
+ --> 1:5
+  |
+1 | Foo(bar)
+  |     ^^^ expected one of: number, boolean
+";
+    assert_eq!(diagnostic.render(source, false), expected);
+  }
+
+  #[test]
+  fn test_render_end_of_input() {
+    let source = "Foo(1,";
+    let failure = Ctx::new(source).skip(6).parse_str(")").unwrap_err();
+    let diagnostic = Diagnostic::from(failure);
+
+    let expected = "\
+error: unexpected end of input
+ --> 1:7
+  |
+1 | Foo(1,
+  |       ^ expected )
+";
+    assert_eq!(diagnostic.render(source, false), expected);
+  }
+
+  #[test]
+  fn test_span_outside_source() {
+    // past the end, or inside the 2 bytes of `ñ`
+    let diagnostic = Diagnostic::unexpected("año", Span::new(2, 9));
+    assert_eq!(diagnostic.message(), "unexpected `ño`");
+    assert_eq!(diagnostic.span(), Span::new(1, 4));
+
+    let diagnostic = Diagnostic::unexpected("año", Span::new(7, 3));
+    assert_eq!(diagnostic.message(), "unexpected end of input");
+
+    let expected = "\
+error: oops
+ --> 1:2
+  |
+1 | año
+  |  ^ expected o
+";
+    let diagnostic = Diagnostic::new("oops", Span::new(2, 3)).with_expected(["o".to_string()]);
+    assert_eq!(diagnostic.render("año", false), expected);
+    let report = Diagnostic::new("oops", Span::new(20, 30)).render("año", false);
+    assert!(report.contains(" --> 1:4\n"));
+  }
+
+  #[test]
+  fn test_render_color() {
+    let source = "x";
+    let diagnostic = Diagnostic::new("unexpected `x`", Span::new(0, 1));
+    let report = diagnostic.render(source, true);
+    assert!(report.starts_with("\x1b[1;31merror\x1b[0m: \x1b[1munexpected `x`\x1b[0m\n"));
+    assert!(report.contains("\x1b[1;31m^\x1b[0m"));
+  }
+}
@@ -0,0 +1,548 @@
+use std::{cmp::Ordering, collections::HashMap, fmt, rc::Rc};
+
+use crate::parser::{parse, BinOp, Expr, UnaryOp};
+
+// What an `Expr` evaluates to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Num(i64),
+  Float(f64),
+  Str(String),
+  Bool(bool),
+  Null,
+}
+
+impl Value {
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Value::Num(_) => "integer",
+      Value::Float(_) => "float",
+      Value::Str(_) => "string",
+      Value::Bool(_) => "boolean",
+      Value::Null => "null",
+    }
+  }
+
+  pub fn as_bool(&self) -> Option<bool> {
+    match self {
+      Value::Bool(b) => Some(*b),
+      _ => None,
+    }
+  }
+
+  pub fn as_num(&self) -> Option<i64> {
+    match self {
+      Value::Num(n) => Some(*n),
+      _ => None,
+    }
+  }
+
+  // integers are widened to floats
+  pub fn as_float(&self) -> Option<f64> {
+    match self {
+      Value::Num(n) => Some(*n as f64),
+      Value::Float(f) => Some(*f),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Value::Str(s) => Some(s),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Value::Num(n) => write!(f, "{n}"),
+      Value::Float(n) => write!(f, "{n:?}"),
+      Value::Str(s) => write!(f, "{s}"),
+      Value::Bool(b) => write!(f, "{b}"),
+      Value::Null => write!(f, "null"),
+    }
+  }
+}
+
+// How many arguments a function takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+  Exact(usize),
+  AtLeast(usize),
+}
+
+impl Arity {
+  pub fn accepts(self, count: usize) -> bool {
+    match self {
+      Arity::Exact(n) => count == n,
+      Arity::AtLeast(n) => count >= n,
+    }
+  }
+}
+
+impl fmt::Display for Arity {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (prefix, n) = match self {
+      Arity::Exact(n) => ("", *n),
+      Arity::AtLeast(n) => ("at least ", *n),
+    };
+    let plural = if n == 1 { "" } else { "s" };
+    write!(f, "{prefix}{n} argument{plural}")
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+  // the code given to `Runtime::eval_str` does not parse
+  Parse(String),
+  UnknownFunction(String),
+  Arity {
+    function: String,
+    expected: Arity,
+    found: usize,
+  },
+  Type {
+    function: String,
+    expected: &'static str,
+    found: &'static str,
+  },
+  DivisionByZero,
+  Overflow,
+  // an `Expr::Error` left by `parser::parse_with_recovery`
+  Invalid,
+  // raised by a native function
+  Custom(String),
+}
+
+impl EvalError {
+  pub fn type_error(function: &str, expected: &'static str, found: &Value) -> EvalError {
+    EvalError::Type {
+      function: function.to_owned(),
+      expected,
+      found: found.type_name(),
+    }
+  }
+}
+
+impl fmt::Display for EvalError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      EvalError::Parse(message) => write!(f, "{message}"),
+      EvalError::UnknownFunction(name) => write!(f, "unknown function `{name}`"),
+      EvalError::Arity {
+        function,
+        expected,
+        found,
+      } => write!(f, "`{function}` takes {expected} but {found} were given"),
+      EvalError::Type {
+        function,
+        expected,
+        found,
+      } => write!(f, "`{function}` expected {expected}, found {found}"),
+      EvalError::DivisionByZero => write!(f, "division by zero"),
+      EvalError::Overflow => write!(f, "integer overflow"),
+      EvalError::Invalid => write!(f, "cannot evaluate an expression that failed to parse"),
+      EvalError::Custom(message) => write!(f, "{message}"),
+    }
+  }
+}
+
+impl std::error::Error for EvalError {}
+
+pub type EvalResult = std::result::Result<Value, EvalError>;
+
+// native functions get their arguments evaluated, lazy ones get the
+// unevaluated `Expr`s and decide what to evaluate (`If`, `And`, `Or`)
+type NativeFn = Rc<dyn Fn(&[Value]) -> EvalResult>;
+type LazyFn = Rc<dyn Fn(&Runtime, &[Expr]) -> EvalResult>;
+
+#[derive(Clone)]
+enum Body {
+  Native(NativeFn),
+  Lazy(LazyFn),
+}
+
+#[derive(Clone)]
+struct Function {
+  arity: Arity,
+  body: Body,
+}
+
+// Evaluates `Expr`s, calls are looked up by `Call::target` in a registry of
+// functions provided by the host
+#[derive(Clone)]
+pub struct Runtime {
+  functions: HashMap<String, Function>,
+}
+
+impl Default for Runtime {
+  fn default() -> Self {
+    Runtime::new()
+  }
+}
+
+impl Runtime {
+  // a runtime with the built-in functions: `Add`, `Sub`, `Mul`, `Div`, `Rem`,
+  // `Eq`, `Ne`, `Lt`, `Le`, `Gt`, `Ge`, `And`, `Or`, `Not`, `Neg` and `If`
+  pub fn new() -> Runtime {
+    let mut runtime = Runtime::empty();
+    for op in [
+      BinOp::Add,
+      BinOp::Sub,
+      BinOp::Mul,
+      BinOp::Div,
+      BinOp::Rem,
+      BinOp::Eq,
+      BinOp::Ne,
+      BinOp::Lt,
+      BinOp::Le,
+      BinOp::Gt,
+      BinOp::Ge,
+    ] {
+      runtime.register(format!("{op:?}"), Arity::Exact(2), move |args| {
+        binary(op, &args[0], &args[1])
+      });
+    }
+    for op in [BinOp::And, BinOp::Or] {
+      runtime.register_lazy(format!("{op:?}"), Arity::Exact(2), move |runtime, args| {
+        runtime.logical(op, &args[0], &args[1])
+      });
+    }
+    for op in [UnaryOp::Not, UnaryOp::Neg] {
+      runtime.register(format!("{op:?}"), Arity::Exact(1), move |args| {
+        unary(op, &args[0])
+      });
+    }
+    runtime.register_lazy("If", Arity::Exact(3), |runtime, args| {
+      match runtime.eval(&args[0])? {
+        Value::Bool(true) => runtime.eval(&args[1]),
+        Value::Bool(false) => runtime.eval(&args[2]),
+        other => Err(EvalError::type_error("If", "boolean", &other)),
+      }
+    });
+    runtime
+  }
+
+  // a runtime without any function
+  pub fn empty() -> Runtime {
+    Runtime {
+      functions: HashMap::new(),
+    }
+  }
+
+  // registers `function` under `name`, replacing any previous one, the
+  // arguments are evaluated and checked against `arity` before the call
+  pub fn register(
+    &mut self,
+    name: impl Into<String>,
+    arity: Arity,
+    function: impl Fn(&[Value]) -> EvalResult + 'static,
+  ) -> &mut Runtime {
+    let body = Body::Native(Rc::new(function));
+    self.functions.insert(name.into(), Function { arity, body });
+    self
+  }
+
+  // like `register` but `function` gets the arguments unevaluated, call
+  // `Runtime::eval` on the ones it needs
+  pub fn register_lazy(
+    &mut self,
+    name: impl Into<String>,
+    arity: Arity,
+    function: impl Fn(&Runtime, &[Expr]) -> EvalResult + 'static,
+  ) -> &mut Runtime {
+    let body = Body::Lazy(Rc::new(function));
+    self.functions.insert(name.into(), Function { arity, body });
+    self
+  }
+
+  pub fn contains(&self, name: &str) -> bool {
+    self.functions.contains_key(name)
+  }
+
+  pub fn eval(&self, expr: &Expr) -> EvalResult {
+    match expr {
+      Expr::Num(n) => Ok(Value::Num(*n)),
+      Expr::Float(f) => Ok(Value::Float(*f)),
+      Expr::Str(s) => Ok(Value::Str(s.clone())),
+      Expr::Bool(b) => Ok(Value::Bool(*b)),
+      Expr::Null => Ok(Value::Null),
+      Expr::Call(call) => self.call(call.target(), call.args()),
+      Expr::Binary(op @ (BinOp::And | BinOp::Or), lhs, rhs) => self.logical(*op, lhs, rhs),
+      Expr::Binary(op, lhs, rhs) => binary(*op, &self.eval(lhs)?, &self.eval(rhs)?),
+      Expr::Unary(op, val) => unary(*op, &self.eval(val)?),
+      Expr::Error => Err(EvalError::Invalid),
+    }
+  }
+
+  // parses and evaluates `code`
+  pub fn eval_str(&self, code: &str) -> EvalResult {
+    let expr = parse(code).map_err(EvalError::Parse)?;
+    self.eval(&expr)
+  }
+
+  fn call(&self, target: &str, args: &[Expr]) -> EvalResult {
+    let function = self
+      .functions
+      .get(target)
+      .ok_or_else(|| EvalError::UnknownFunction(target.to_owned()))?;
+    if !function.arity.accepts(args.len()) {
+      return Err(EvalError::Arity {
+        function: target.to_owned(),
+        expected: function.arity,
+        found: args.len(),
+      });
+    }
+
+    match &function.body {
+      Body::Native(native) => {
+        let values = args
+          .iter()
+          .map(|arg| self.eval(arg))
+          .collect::<Result<Vec<_>, _>>()?;
+        native(&values)
+      }
+      Body::Lazy(lazy) => lazy(self, args),
+    }
+  }
+
+  // `&&` and `||`, the right side is only evaluated when needed
+  fn logical(&self, op: BinOp, lhs: &Expr, rhs: &Expr) -> EvalResult {
+    let name = format!("{op:?}");
+    let boolean = |val: Value| {
+      val
+        .as_bool()
+        .ok_or_else(|| EvalError::type_error(&name, "boolean", &val))
+    };
+
+    let lhs = boolean(self.eval(lhs)?)?;
+    if lhs == (op == BinOp::Or) {
+      return Ok(Value::Bool(lhs));
+    }
+    Ok(Value::Bool(boolean(self.eval(rhs)?)?))
+  }
+}
+
+// integers stay integers and fail on overflow, mixing in a float gives a float
+fn binary(op: BinOp, lhs: &Value, rhs: &Value) -> EvalResult {
+  let name = format!("{op:?}");
+  match (op, lhs, rhs) {
+    (BinOp::Eq, _, _) => Ok(Value::Bool(equals(lhs, rhs))),
+    (BinOp::Ne, _, _) => Ok(Value::Bool(!equals(lhs, rhs))),
+    (BinOp::Lt, _, _) => Ok(Value::Bool(
+      compare(&name, lhs, rhs)? == Some(Ordering::Less),
+    )),
+    (BinOp::Le, _, _) => Ok(Value::Bool(matches!(
+      compare(&name, lhs, rhs)?,
+      Some(Ordering::Less | Ordering::Equal)
+    ))),
+    (BinOp::Gt, _, _) => Ok(Value::Bool(
+      compare(&name, lhs, rhs)? == Some(Ordering::Greater),
+    )),
+    (BinOp::Ge, _, _) => Ok(Value::Bool(matches!(
+      compare(&name, lhs, rhs)?,
+      Some(Ordering::Greater | Ordering::Equal)
+    ))),
+    (BinOp::And | BinOp::Or, _, _) => {
+      let boolean = |val: &Value| {
+        val
+          .as_bool()
+          .ok_or_else(|| EvalError::type_error(&name, "boolean", val))
+      };
+      let (lhs, rhs) = (boolean(lhs)?, boolean(rhs)?);
+      Ok(Value::Bool(if op == BinOp::And {
+        lhs && rhs
+      } else {
+        lhs || rhs
+      }))
+    }
+    (BinOp::Add, Value::Str(a), Value::Str(b)) => Ok(Value::Str(format!("{a}{b}"))),
+    (BinOp::Div | BinOp::Rem, Value::Num(_), Value::Num(0)) => Err(EvalError::DivisionByZero),
+    (BinOp::Add, _, _) => arithmetic(&name, lhs, rhs, i64::checked_add, |a, b| a + b),
+    (BinOp::Sub, _, _) => arithmetic(&name, lhs, rhs, i64::checked_sub, |a, b| a - b),
+    (BinOp::Mul, _, _) => arithmetic(&name, lhs, rhs, i64::checked_mul, |a, b| a * b),
+    (BinOp::Div, _, _) => arithmetic(&name, lhs, rhs, i64::checked_div, |a, b| a / b),
+    (BinOp::Rem, _, _) => arithmetic(&name, lhs, rhs, i64::checked_rem, |a, b| a % b),
+  }
+}
+
+fn arithmetic(
+  name: &str,
+  lhs: &Value,
+  rhs: &Value,
+  int: fn(i64, i64) -> Option<i64>,
+  float: fn(f64, f64) -> f64,
+) -> EvalResult {
+  if let (Value::Num(a), Value::Num(b)) = (lhs, rhs) {
+    return int(*a, *b).map(Value::Num).ok_or(EvalError::Overflow);
+  }
+  Ok(Value::Float(float(number(name, lhs)?, number(name, rhs)?)))
+}
+
+fn unary(op: UnaryOp, val: &Value) -> EvalResult {
+  let name = format!("{op:?}");
+  match (op, val) {
+    (UnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+    (UnaryOp::Not, _) => Err(EvalError::type_error(&name, "boolean", val)),
+    (UnaryOp::Neg, Value::Num(n)) => n.checked_neg().map(Value::Num).ok_or(EvalError::Overflow),
+    (UnaryOp::Neg, _) => Ok(Value::Float(-number(&name, val)?)),
+  }
+}
+
+fn number(name: &str, val: &Value) -> Result<f64, EvalError> {
+  val
+    .as_float()
+    .ok_or_else(|| EvalError::type_error(name, "number", val))
+}
+
+// integers and floats compare by their numeric value
+fn equals(lhs: &Value, rhs: &Value) -> bool {
+  match (lhs, rhs) {
+    (Value::Num(_), Value::Float(_)) | (Value::Float(_), Value::Num(_)) => {
+      lhs.as_float() == rhs.as_float()
+    }
+    _ => lhs == rhs,
+  }
+}
+
+// numbers and strings are ordered, `None` when a float is NaN
+fn compare(name: &str, lhs: &Value, rhs: &Value) -> Result<Option<Ordering>, EvalError> {
+  match (lhs, rhs) {
+    (Value::Num(a), Value::Num(b)) => Ok(Some(a.cmp(b))),
+    (Value::Str(a), Value::Str(b)) => Ok(Some(a.cmp(b))),
+    _ => Ok(number(name, lhs)?.partial_cmp(&number(name, rhs)?)),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::parser::parse_with_recovery;
+
+  fn eval(code: &str) -> EvalResult {
+    Runtime::new().eval_str(code)
+  }
+
+  #[test]
+  fn test_literals() {
+    assert_eq!(eval("1"), Ok(Value::Num(1)));
+    assert_eq!(eval("1.5"), Ok(Value::Float(1.5)));
+    assert_eq!(eval(r#""a""#), Ok(Value::Str("a".to_owned())));
+    assert_eq!(eval("true"), Ok(Value::Bool(true)));
+    assert_eq!(eval("null"), Ok(Value::Null));
+  }
+
+  #[test]
+  fn test_operators() {
+    assert_eq!(eval("1 + 2 * 3"), Ok(Value::Num(7)));
+    assert_eq!(eval("7 / 2"), Ok(Value::Num(3)));
+    assert_eq!(eval("7 % 2"), Ok(Value::Num(1)));
+    assert_eq!(eval("1 + 0.5"), Ok(Value::Float(1.5)));
+    assert_eq!(eval("-(1.5)"), Ok(Value::Float(-1.5)));
+    assert_eq!(eval(r#""a" + "b""#), Ok(Value::Str("ab".to_owned())));
+    assert_eq!(eval("1 == 1.0"), Ok(Value::Bool(true)));
+    assert_eq!(eval(r#"1 != "1""#), Ok(Value::Bool(true)));
+    assert_eq!(eval(r#""a" < "b" && 2 >= 1.5"#), Ok(Value::Bool(true)));
+    assert_eq!(eval("!(1 > 2)"), Ok(Value::Bool(true)));
+
+    assert_eq!(eval("1 / 0"), Err(EvalError::DivisionByZero));
+    assert_eq!(eval("9223372036854775807 + 1"), Err(EvalError::Overflow));
+    assert_eq!(
+      eval(r#"1 + "a""#),
+      Err(EvalError::Type {
+        function: "Add".to_owned(),
+        expected: "number",
+        found: "string"
+      })
+    );
+
+    // the right side is not evaluated when the left one decides
+    assert_eq!(eval("false && 1 / 0 == 0"), Ok(Value::Bool(false)));
+    assert_eq!(eval("true || Missing()"), Ok(Value::Bool(true)));
+  }
+
+  #[test]
+  fn test_builtins() {
+    assert_eq!(eval("Add(1, Mul(2, 3))"), Ok(Value::Num(7)));
+    assert_eq!(eval("Eq(Sub(3, 1), 2)"), Ok(Value::Bool(true)));
+    assert_eq!(eval("Not(Lt(2, 1))"), Ok(Value::Bool(true)));
+    assert_eq!(
+      eval(r#"If(1 < 2, "yes", 1 / 0)"#),
+      Ok(Value::Str("yes".to_owned()))
+    );
+    assert_eq!(eval("Or(false, And(true, true))"), Ok(Value::Bool(true)));
+
+    assert_eq!(
+      eval("If(1, 2, 3)"),
+      Err(EvalError::Type {
+        function: "If".to_owned(),
+        expected: "boolean",
+        found: "integer"
+      })
+    );
+    assert_eq!(
+      eval("Not(true, false)"),
+      Err(EvalError::Arity {
+        function: "Not".to_owned(),
+        expected: Arity::Exact(1),
+        found: 2
+      })
+    );
+    assert_eq!(
+      eval("Foo()"),
+      Err(EvalError::UnknownFunction("Foo".to_owned()))
+    );
+  }
+
+  #[test]
+  fn test_register() {
+    let mut runtime = Runtime::new();
+    runtime
+      .register("Concat", Arity::AtLeast(1), |args| {
+        Ok(Value::Str(args.iter().map(Value::to_string).collect()))
+      })
+      .register("Len", Arity::Exact(1), |args| match &args[0] {
+        Value::Str(s) => Ok(Value::Num(s.chars().count() as i64)),
+        other => Err(EvalError::type_error("Len", "string", other)),
+      });
+    assert!(runtime.contains("Concat"));
+
+    assert_eq!(
+      runtime.eval_str(r#"Concat("a", 1, 2.0, null)"#),
+      Ok(Value::Str("a12.0null".to_owned()))
+    );
+    assert_eq!(runtime.eval_str(r#"Len("ñu") + 1"#), Ok(Value::Num(3)));
+
+    let err = runtime.eval_str("Concat()").unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "`Concat` takes at least 1 argument but 0 were given"
+    );
+    let err = runtime.eval_str("Len(1)").unwrap_err();
+    assert_eq!(err.to_string(), "`Len` expected string, found integer");
+
+    // host functions can replace the built-in ones
+    runtime.register("Add", Arity::Exact(2), |_| {
+      Err(EvalError::Custom("disabled".to_owned()))
+    });
+    assert_eq!(
+      runtime.eval_str("Add(1, 2)"),
+      Err(EvalError::Custom("disabled".to_owned()))
+    );
+    assert_eq!(runtime.eval_str("1 + 2"), Ok(Value::Num(3)));
+  }
+
+  #[test]
+  fn test_errors() {
+    assert!(matches!(eval("Foo("), Err(EvalError::Parse(_))));
+
+    let (expr, _) = parse_with_recovery("Add(1, @)");
+    assert_eq!(Runtime::new().eval(&expr.unwrap()), Err(EvalError::Invalid));
+
+    let runtime = Runtime::empty();
+    assert!(!runtime.contains("Add"));
+    assert_eq!(runtime.eval_str("1 + 2"), Ok(Value::Num(3)));
+  }
+}
@@ -1,26 +1,192 @@
+use crate::{
+  diagnostics::Diagnostic,
+  trace::{Trace, Tracer},
+};
+use regex_automata::Input;
+
+// the regexes `parse_regex` takes, set flags with `Regex::builder`
+pub use regex_automata::meta::Regex;
+use std::{any::Any, any::TypeId, cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+// A byte range `start..end` of the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+}
+
+impl Span {
+  pub fn new(start: usize, end: usize) -> Span {
+    Span { start, end }
+  }
+
+  pub fn len(&self) -> usize {
+    self.end - self.start
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.start == self.end
+  }
+}
+
+// 1-based (line, column) of a byte offset, columns are counted in chars
+pub fn line_col(text: &str, index: usize) -> (usize, usize) {
+  // an index inside a multi-byte char counts as that char's position
+  let mut index = index.min(text.len());
+  while !text.is_char_boundary(index) {
+    index -= 1;
+  }
+  let before = &text[..index];
+  let line = before.matches('\n').count() + 1;
+  let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+  (line, before[line_start..].chars().count() + 1)
+}
+
+// The source text is shared between every `Ctx` derived from the same input,
+// so advancing only copies a pointer and an index.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Ctx {
-  text: String,
+  text: Rc<str>,
   index: usize,
+  recovering: bool,
+  diagnostics: Option<Rc<Recorded>>,
+  // `None` only in the copies kept by the memo table itself
+  memo: Option<Rc<Memo>>,
+  // only set by `with_trace`
+  trace: Option<Rc<Tracer>>,
+}
+
+// Diagnostics recorded while recovering, newest first. Being part of `Ctx`,
+// the ones recorded on a path that gets backtracked are dropped with it.
+#[derive(Debug, PartialEq, Eq)]
+struct Recorded {
+  diagnostic: Diagnostic,
+  prev: Option<Rc<Recorded>>,
+}
+
+// Identifies a memoized parser, see `combinator::memo`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum MemoId {
+  // a parser without state, every instance of the type parses the same way
+  Type(TypeId),
+  // one particular parser instance
+  Instance(usize),
+}
+
+// The results of memoized parsers by parser and position, shared by every
+// `Ctx` of the same parse in the same mode: `with_recovery` and `with_trace`
+// start a new one. It is a cache, not part of the `Ctx` identity.
+#[derive(Default)]
+struct Memo {
+  entries: RefCell<HashMap<(MemoId, usize), Box<dyn Any>>>,
+}
+
+// A memoized result with its `Ctx`s detached from the table, which would
+// otherwise own itself. It only stands for the diagnostics it started with.
+struct MemoEntry<T> {
+  diagnostics: Option<Rc<Recorded>>,
+  result: Result<T>,
+}
+
+impl fmt::Debug for Memo {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Memo({} entries)", self.entries.borrow().len())
+  }
+}
+
+impl PartialEq for Memo {
+  fn eq(&self, _other: &Memo) -> bool {
+    true
+  }
 }
 
+impl Eq for Memo {}
+
 impl Ctx {
   pub fn new(text: &str) -> Ctx {
     Ctx {
-      text: text.to_string(),
+      text: Rc::from(text),
       index: 0,
+      recovering: false,
+      diagnostics: None,
+      memo: Some(Rc::default()),
+      trace: None,
+    }
+  }
+
+  // lets `combinator::recover` record committed failures and keep parsing.
+  // The memo table starts empty, results parsed without recovery may differ.
+  pub fn with_recovery(self) -> Ctx {
+    Ctx {
+      recovering: true,
+      memo: Some(Rc::default()),
+      ..self
+    }
+  }
+
+  // records every `combinator::named` parser run from this `Ctx` on, read
+  // it back with `trace`. The memo table starts empty so that no result
+  // parsed untraced skips the parsers it would have recorded.
+  pub fn with_trace(self) -> Ctx {
+    Ctx {
+      trace: Some(Rc::default()),
+      memo: Some(Rc::default()),
+      ..self
+    }
+  }
+
+  // the named parsers finished so far, `None` without `with_trace`
+  pub fn trace(&self) -> Option<Trace> {
+    let tracer = self.trace.as_ref()?;
+    Some(tracer.trace(Rc::clone(&self.text)))
+  }
+
+  pub(crate) fn tracer(&self) -> Option<&Tracer> {
+    self.trace.as_deref()
+  }
+
+  pub fn is_recovering(&self) -> bool {
+    self.recovering
+  }
+
+  pub fn record(&self, diagnostic: Diagnostic) -> Ctx {
+    let prev = self.diagnostics.clone();
+    Ctx {
+      diagnostics: Some(Rc::new(Recorded { diagnostic, prev })),
+      ..self.clone()
+    }
+  }
+
+  // the recorded diagnostics in the order they were found
+  pub fn diagnostics(&self) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut node = self.diagnostics.as_deref();
+    while let Some(recorded) = node {
+      diagnostics.push(recorded.diagnostic.clone());
+      node = recorded.prev.as_deref();
     }
+    diagnostics.reverse();
+    diagnostics
   }
 
-  fn next(&self, index: usize) -> Self {
+  // a position inside a multi-byte char moves to the end of that char, so
+  // `text_slice` can always slice there
+  pub(crate) fn next(&self, index: usize) -> Self {
     let mut new_index = index;
     if new_index > self.text.len() {
       new_index = self.text.len();
     }
+    while !self.text.is_char_boundary(new_index) {
+      new_index += 1;
+    }
 
     Self {
-      text: self.text.clone(),
+      text: Rc::clone(&self.text),
       index: new_index,
+      recovering: self.recovering,
+      diagnostics: self.diagnostics.clone(),
+      memo: self.memo.clone(),
+      trace: self.trace.clone(),
     }
   }
 
@@ -28,9 +194,16 @@ impl Ctx {
     self.next(self.index + count)
   }
 
+  // an empty match at the current position
   pub fn success<T>(&self, val: T) -> Success<T> {
+    self.success_from(self.index, val)
+  }
+
+  // a match that started at `start` and ends at the current position
+  pub fn success_from<T>(&self, start: usize, val: T) -> Success<T> {
     Success {
       ctx: self.clone(),
+      span: Span::new(start.min(self.index), self.index),
       val,
     }
   }
@@ -38,44 +211,221 @@ impl Ctx {
   pub fn failure(&self, expected: String) -> Failure {
     Failure {
       ctx: self.clone(),
-      expected,
+      expected: vec![expected],
+      committed: false,
     }
   }
 
+  pub fn index(&self) -> usize {
+    self.index
+  }
+
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+
+  // 1-based line of the current position, computed on demand
+  pub fn line(&self) -> usize {
+    line_col(&self.text, self.index).0
+  }
+
+  // 1-based column of the current position, counted in chars
+  pub fn column(&self) -> usize {
+    line_col(&self.text, self.index).1
+  }
+
+  // the word (or single char) starting at the current position
+  pub fn token_span(&self) -> Span {
+    let text = self.text_slice();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let len = match text.chars().next() {
+      None => 0,
+      Some(c) if is_word(c) => text.find(|c| !is_word(c)).unwrap_or(text.len()),
+      Some(c) => c.len_utf8(),
+    };
+    Span::new(self.index, self.index + len)
+  }
+
   pub fn text_slice(&self) -> &str {
     &self.text[self.index..]
   }
 
-  pub fn parse_str(&self, r#match: String) -> Result<String> {
-    let len = r#match.len();
-    let text = self.text_slice();
-    if len > text.len() {
-      return Err(self.failure(r#match));
+  // the result of the parser `id` at the current position, computed by
+  // `parser` only the first time it is reached with the same diagnostics
+  pub(crate) fn memoized<T: Clone + 'static>(
+    &self,
+    id: MemoId,
+    parser: impl Fn(&Ctx) -> Result<T>,
+  ) -> Result<T> {
+    if let Some(result) = self.memo_get(id) {
+      return result;
+    }
+    // the table is not borrowed while `parser` runs, it may be reentered
+    let result = parser(self);
+    self.memo_put(id, &result);
+    result
+  }
+
+  // Like `memoized` for a left-recursive `parser`, by growing a seed: the
+  // recursive call at the same position first finds a failure, so `parser`
+  // takes a non-recursive alternative. The result is then stored in its place
+  // and `parser` is run again as long as it gets further.
+  pub(crate) fn grown<T: Clone + 'static>(
+    &self,
+    id: MemoId,
+    parser: impl Fn(&Ctx) -> Result<T>,
+  ) -> Result<T> {
+    if let Some(result) = self.memo_get(id) {
+      return result;
+    }
+    // the seed expects nothing, it adds nothing to the reported failures
+    let seed = Failure {
+      ctx: self.clone(),
+      expected: vec![],
+      committed: false,
+    };
+    self.memo_put::<T>(id, &Err(seed));
+    let mut best = parser(self);
+    while let Ok(success) = &best {
+      let end = success.index();
+      self.memo_put(id, &best);
+      match parser(self) {
+        Ok(next) if next.index() > end => best = Ok(next),
+        Err(failure) if failure.is_committed() => best = Err(failure),
+        _ => break,
+      }
     }
+    self.memo_put(id, &best);
+    best
+  }
 
-    if text[0..len] == r#match {
-      Ok(self.skip(len).success(r#match))
+  fn memo_get<T: Clone + 'static>(&self, id: MemoId) -> Option<Result<T>> {
+    let memo = self.memo.as_ref()?;
+    let entries = memo.entries.borrow();
+    let entry = entries
+      .get(&(id, self.index))?
+      .downcast_ref::<MemoEntry<T>>()?;
+    let same_diagnostics = match (&entry.diagnostics, &self.diagnostics) {
+      (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+      (a, b) => a.is_none() && b.is_none(),
+    };
+    same_diagnostics.then(|| self.attach(entry.result.clone()))
+  }
+
+  fn memo_put<T: Clone + 'static>(&self, id: MemoId, result: &Result<T>) {
+    if let Some(memo) = &self.memo {
+      let entry = MemoEntry {
+        diagnostics: self.diagnostics.clone(),
+        result: self.detach(result.clone()),
+      };
+      memo
+        .entries
+        .borrow_mut()
+        .insert((id, self.index), Box::new(entry));
+    }
+  }
+
+  fn detach<T>(&self, result: Result<T>) -> Result<T> {
+    match result {
+      Ok(success) => Ok(Success {
+        ctx: Ctx {
+          memo: None,
+          ..success.ctx
+        },
+        ..success
+      }),
+      Err(failure) => Err(Failure {
+        ctx: Ctx {
+          memo: None,
+          ..failure.ctx
+        },
+        ..failure
+      }),
+    }
+  }
+
+  fn attach<T>(&self, result: Result<T>) -> Result<T> {
+    let memo = self.memo.clone();
+    match result {
+      Ok(success) => Ok(Success {
+        ctx: Ctx {
+          memo,
+          ..success.ctx
+        },
+        ..success
+      }),
+      Err(failure) => Err(Failure {
+        ctx: Ctx {
+          memo,
+          ..failure.ctx
+        },
+        ..failure
+      }),
+    }
+  }
+
+  pub fn parse_str(&self, r#match: &str) -> Result<String> {
+    if self.text_slice().starts_with(r#match) {
+      let val = r#match.to_owned();
+      Ok(self.skip(r#match.len()).success_from(self.index, val))
     } else {
-      Err(self.failure(r#match))
+      Err(self.failure(r#match.to_owned()))
     }
   }
 
-  pub fn parse_regex(&self, re: regex::Regex, expected: String) -> Result<String> {
-    fn get_firts_match(text: &str, re: regex::Regex) -> Option<regex::Match> {
-      let captures = re.captures(text)?;
-      let first = captures.get(0)?;
-      if first.start() != 0 {
-        return None;
+  // like `parse_str` but chars are compared in lowercase, one by one, the
+  // value is the text as written in the input
+  pub fn parse_str_no_case(&self, r#match: &str) -> Result<String> {
+    let text = self.text_slice();
+    let mut chars = text.char_indices();
+    let mut len = 0;
+    for expected in r#match.chars() {
+      match chars.next() {
+        Some((_, c)) if c.to_lowercase().eq(expected.to_lowercase()) => len += c.len_utf8(),
+        _ => return Err(self.failure(r#match.to_owned())),
       }
-      Some(first)
     }
+    let val = text[..len].to_owned();
+    Ok(self.skip(len).success_from(self.index, val))
+  }
 
+  // matches `re` at the current position only, as an anchored search: a miss
+  // costs no more than the longest prefix `re` could match, never a scan of
+  // the rest of the text
+  pub fn parse_regex(&self, re: &Regex, expected: String) -> Result<String> {
     let text = self.text_slice();
-    match get_firts_match(text, re) {
-      Some(first) => Ok(self.skip(first.end()).success(first.as_str().to_string())),
+    match re.search(&anchored(text)) {
+      Some(found) => {
+        let val = text[found.range()].to_string();
+        Ok(self.skip(found.end()).success_from(self.index, val))
+      }
       None => Err(self.failure(expected)),
     }
   }
+
+  // like `parse_regex` with every group of `re`, the whole match first and
+  // `None` for the groups that took no part in it
+  pub fn parse_regex_captures(&self, re: &Regex, expected: String) -> Result<Vec<Option<String>>> {
+    let text = self.text_slice();
+    let mut captures = re.create_captures();
+    re.search_captures(&anchored(text), &mut captures);
+    let Some(found) = captures.get_match() else {
+      return Err(self.failure(expected));
+    };
+    let groups: Vec<_> = (0..captures.group_len())
+      .map(|group| {
+        captures
+          .get_group(group)
+          .map(|span| text[span.range()].to_string())
+      })
+      .collect();
+    Ok(self.skip(found.end()).success_from(self.index, groups))
+  }
+}
+
+// a search of `text` that only matches at its start
+fn anchored(text: &str) -> Input<'_> {
+  Input::new(text).anchored(regex_automata::Anchored::Yes)
 }
 
 pub type Result<T> = std::result::Result<Success<T>, Failure>;
@@ -83,6 +433,7 @@ pub type Result<T> = std::result::Result<Success<T>, Failure>;
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Success<T> {
   ctx: Ctx,
+  span: Span,
   val: T,
 }
 
@@ -98,6 +449,32 @@ impl<T> Success<T> {
     self.val.to_owned()
   }
 
+  pub fn into_val(self) -> T {
+    self.val
+  }
+
+  pub fn into_parts(self) -> (Ctx, T) {
+    (self.ctx, self.val)
+  }
+
+  pub fn span(&self) -> Span {
+    self.span
+  }
+
+  // moves to `ctx` keeping the value and span, used to skip what follows a token
+  pub fn with_ctx(self, ctx: Ctx) -> Success<T> {
+    Success { ctx, ..self }
+  }
+
+  // transforms the value keeping the position and span
+  pub fn map<U>(self, op: impl FnOnce(T) -> U) -> Success<U> {
+    Success {
+      ctx: self.ctx,
+      span: self.span,
+      val: op(self.val),
+    }
+  }
+
   // visible for testing
   #[allow(dead_code)]
   pub fn index(&self) -> usize {
@@ -108,27 +485,92 @@ impl<T> Success<T> {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Failure {
   ctx: Ctx,
-  expected: String,
+  expected: Vec<String>,
+  // committed failures stop backtracking, `any` and `many` give up on them
+  // instead of trying the next alternative
+  committed: bool,
 }
 
 impl Failure {
+  // "a" for a single item, "one of: a, b, c" for several
   pub fn expected(&self) -> String {
-    self.expected.clone()
+    match self.expected.as_slice() {
+      [one] => one.clone(),
+      many => format!("one of: {}", many.join(", ")),
+    }
+  }
+
+  pub fn expected_set(&self) -> &[String] {
+    &self.expected
+  }
+
+  pub fn is_committed(&self) -> bool {
+    self.committed
+  }
+
+  pub fn commit(self) -> Failure {
+    Failure {
+      committed: true,
+      ..self
+    }
+  }
+
+  // replaces the expected set keeping the position
+  pub fn relabel(self, expected: String) -> Failure {
+    Failure {
+      expected: vec![expected],
+      ..self
+    }
+  }
+
+  // keeps the failure that got further, or the union of both expected sets
+  // when they failed at the same position
+  pub fn merge(mut self, other: Failure) -> Failure {
+    if other.index() > self.index() {
+      return other;
+    }
+    if other.index() == self.index() {
+      for item in other.expected {
+        if !self.expected.contains(&item) {
+          self.expected.push(item);
+        }
+      }
+    }
+    self
   }
 
   pub fn index(&self) -> usize {
     self.ctx.index
   }
+
+  pub fn line(&self) -> usize {
+    self.ctx.line()
+  }
+
+  pub fn column(&self) -> usize {
+    self.ctx.column()
+  }
+
+  // the offending token
+  pub fn span(&self) -> Span {
+    self.ctx.token_span()
+  }
+
+  pub fn ctx(&self) -> &Ctx {
+    &self.ctx
+  }
 }
 
 #[cfg(test)]
 mod ctx_test {
-  use super::Ctx;
+  use super::{line_col, Ctx, Regex, Span};
+  use crate::diagnostics::Diagnostic;
+  use regex_automata::util::syntax;
 
   #[test]
   fn test_new() {
     let ctx = Ctx::new("Hello, world!");
-    assert_eq!(ctx.text, "Hello, world!");
+    assert_eq!(&*ctx.text, "Hello, world!");
     assert_eq!(ctx.index, 0);
   }
 
@@ -136,11 +578,11 @@ mod ctx_test {
   fn test_next() {
     let ctx = Ctx::new("Hello, world!");
     let ctx = ctx.next(5);
-    assert_eq!(ctx.text, "Hello, world!");
+    assert_eq!(&*ctx.text, "Hello, world!");
     assert_eq!(ctx.index, 5);
 
     let ctx = ctx.next(80);
-    assert_eq!(ctx.text, "Hello, world!");
+    assert_eq!(&*ctx.text, "Hello, world!");
     assert_eq!(ctx.index, ctx.text.len());
   }
 
@@ -148,49 +590,83 @@ mod ctx_test {
   fn test_skip() {
     let ctx = Ctx::new("Hello, world!");
     let ctx = ctx.skip(5);
-    assert_eq!(ctx.text, "Hello, world!");
+    assert_eq!(&*ctx.text, "Hello, world!");
     assert_eq!(ctx.index, 5);
 
     let ctx = ctx.skip(2);
-    assert_eq!(ctx.text, "Hello, world!");
+    assert_eq!(&*ctx.text, "Hello, world!");
     assert_eq!(ctx.index, 7);
 
     let ctx = ctx.skip(30);
-    assert_eq!(ctx.text, "Hello, world!");
+    assert_eq!(&*ctx.text, "Hello, world!");
     assert_eq!(ctx.index, ctx.text.len());
+
+    // never inside a char, "ñ" and "€" take 2 and 3 bytes
+    let ctx = Ctx::new("ñ€a");
+    assert_eq!(ctx.skip(1).index, 2);
+    assert_eq!(ctx.skip(1).text_slice(), "€a");
+    assert_eq!(ctx.skip(3).index, 5);
+    assert_eq!(ctx.skip(4).text_slice(), "a");
+    assert_eq!(ctx.next(5).index, 5);
   }
 
   #[test]
   fn test_parse_str_success() {
     let ctx = Ctx::new("Hello, world!");
 
-    let success = ctx.parse_str("Hello".to_string()).unwrap();
+    let success = ctx.parse_str("Hello").unwrap();
 
     assert_eq!(success.val(), "Hello");
     assert_eq!(success.index(), 5);
+    assert_eq!(success.span(), Span::new(0, 5));
   }
 
   #[test]
   fn test_parse_str_failure() {
     let ctx = Ctx::new("Hello, world!");
 
-    let failure = ctx.parse_str("world".to_string()).unwrap_err();
+    let failure = ctx.parse_str("world").unwrap_err();
 
     assert_eq!(failure.expected(), "world");
     assert_eq!(failure.index(), 0);
+
+    // the match length can fall inside a multi-byte char of the text
+    let failure = Ctx::new("ñu").parse_str("ab").unwrap_err();
+    assert_eq!(failure.index(), 0);
+  }
+
+  #[test]
+  fn test_parse_str_no_case() {
+    let ctx = Ctx::new("SELECT * from t");
+    let success = ctx.parse_str_no_case("select").unwrap();
+    assert_eq!(success.val(), "SELECT");
+    assert_eq!(success.span(), Span::new(0, 6));
+
+    let success = ctx.skip(9).parse_str_no_case("FROM").unwrap();
+    assert_eq!(success.val(), "from");
+
+    let failure = ctx.parse_str_no_case("selected").unwrap_err();
+    assert_eq!(failure.expected(), "selected");
+    assert_eq!(failure.index(), 0);
+
+    // the input may spell a char with another length, `ß` and `ẞ` take 2 and 3 bytes
+    let success = Ctx::new("STRAẞE").parse_str_no_case("straße").unwrap();
+    assert_eq!(success.val(), "STRAẞE");
+    assert_eq!(success.index(), 8);
   }
 
   #[test]
   fn test_parse_regex_success() {
     let ctx = Ctx::new("Hello, world!");
 
-    let re = regex::Regex::new(r"[A-Za-z]+").unwrap();
+    let re = Regex::new(r"[A-Za-z]+").unwrap();
 
-    let parser = |ctx: &Ctx| ctx.parse_regex(re.clone(), "A word".to_string());
+    let parser = |ctx: &Ctx| ctx.parse_regex(&re, "A word".to_string());
 
     let success = parser(&ctx).unwrap();
     assert_eq!(success.val(), "Hello");
     assert_eq!(success.index(), 5);
+    assert_eq!(success.span(), Span::new(0, 5));
 
     let ctx = success.ctx().skip(2);
     let result = parser(&ctx);
@@ -198,6 +674,7 @@ mod ctx_test {
       Ok(success) => {
         assert_eq!(success.val(), "world");
         assert_eq!(success.index(), 12);
+        assert_eq!(success.span(), Span::new(7, 12));
       }
       Err(failure) => panic!("{:?}", failure),
     }
@@ -207,14 +684,114 @@ mod ctx_test {
   fn test_parse_regex_failure() {
     let ctx = Ctx::new("Hello, world!").skip(5);
 
-    let re = regex::Regex::new(r"^[0-9]+$").unwrap();
+    let re = Regex::new(r"^[0-9]+$").unwrap();
 
-    let failure = ctx.parse_regex(re, "A number".to_string()).unwrap_err();
+    let failure = ctx.parse_regex(&re, "A number".to_string()).unwrap_err();
 
     assert_eq!(failure.expected(), "A number");
     assert_eq!(failure.index(), 5);
   }
 
+  #[test]
+  fn test_parse_regex_anchored() {
+    // a match further in the text is not a match
+    let re = Regex::new(r"[0-9]+").unwrap();
+    let failure = Ctx::new("abc 123")
+      .parse_regex(&re, "number".to_string())
+      .unwrap_err();
+    assert_eq!(failure.index(), 0);
+
+    // neither through an alternative
+    let re = Regex::new(r"x|[0-9]+").unwrap();
+    assert!(Ctx::new("abc 123")
+      .parse_regex(&re, "".to_string())
+      .is_err());
+    let success = Ctx::new("123x").parse_regex(&re, "".to_string()).unwrap();
+    assert_eq!(success.val(), "123");
+  }
+
+  #[test]
+  fn test_parse_regex_captures() {
+    let re = Regex::new(r"([a-z]+)(=([0-9]+))?").unwrap();
+    let ctx = Ctx::new("x width=80");
+
+    let success = ctx
+      .skip(2)
+      .parse_regex_captures(&re, "setting".to_string())
+      .unwrap();
+    let groups = ["width=80", "width", "=80", "80"].map(|g| Some(g.to_string()));
+    assert_eq!(success.val(), groups);
+    assert_eq!(success.span(), Span::new(2, 10));
+
+    let success = ctx
+      .parse_regex_captures(&re, "setting".to_string())
+      .unwrap();
+    assert_eq!(
+      success.val(),
+      [Some("x".to_string()), Some("x".to_string()), None, None]
+    );
+
+    let failure = ctx
+      .skip(1)
+      .parse_regex_captures(&re, "setting".to_string())
+      .unwrap_err();
+    assert_eq!(failure.expected(), "setting");
+    assert_eq!(failure.index(), 1);
+  }
+
+  #[test]
+  fn test_parse_regex_linear() {
+    // a miss at each of 100k positions of a 1MB text, with the only digits at
+    // the very end: rescanning the rest of the text for them on every miss
+    // would be 5e10 steps
+    let text = "a".repeat(1_000_000) + "42";
+    let ctx = Ctx::new(&text);
+    let re = Regex::new(r"[0-9]+").unwrap();
+    for index in (0..1_000_000).step_by(10) {
+      let failure = ctx
+        .skip(index)
+        .parse_regex(&re, "number".to_string())
+        .unwrap_err();
+      assert_eq!(failure.index(), index);
+      let failure = ctx
+        .skip(index)
+        .parse_regex_captures(&re, "number".to_string())
+        .unwrap_err();
+      assert_eq!(failure.index(), index);
+    }
+    let success = ctx
+      .skip(1_000_000)
+      .parse_regex(&re, "number".to_string())
+      .unwrap();
+    assert_eq!(success.val(), "42");
+  }
+
+  #[test]
+  fn test_parse_regex_flags() {
+    // anchoring keeps the options `re` was built with
+    let re = Regex::builder()
+      .syntax(syntax::Config::new().case_insensitive(true))
+      .build("abc")
+      .unwrap();
+    let success = Ctx::new("ABC").parse_regex(&re, "abc".to_string()).unwrap();
+    assert_eq!(success.val(), "ABC");
+    let success = Ctx::new("aBc")
+      .parse_regex_captures(&re, "abc".to_string())
+      .unwrap();
+    assert_eq!(success.val(), [Some("aBc".to_string())]);
+    assert!(Ctx::new("xabc")
+      .parse_regex(&re, "abc".to_string())
+      .is_err());
+  }
+
+  #[test]
+  fn test_skip_shares_text() {
+    let ctx = Ctx::new("Hello, world!");
+    let next = ctx.skip(5);
+    assert!(std::rc::Rc::ptr_eq(&ctx.text, &next.text));
+    assert!(std::rc::Rc::ptr_eq(&ctx.text, &next.success(()).ctx.text));
+  }
+
   #[test]
   fn test_ctx_compare() {
     let ctx = Ctx::new("Hello, world!");
@@ -227,7 +804,7 @@ mod ctx_test {
   fn test_success() {
     let ctx = Ctx::new("Hello, world!");
     let ctx = ctx.next(5).success("Hello".to_string());
-    assert_eq!(ctx.ctx.text, "Hello, world!");
+    assert_eq!(&*ctx.ctx.text, "Hello, world!");
     assert_eq!(ctx.ctx.index, 5);
     assert_eq!(ctx.val, "Hello");
   }
@@ -236,9 +813,9 @@ mod ctx_test {
   fn test_failure() {
     let ctx = Ctx::new("Hello, world!");
     let ctx = ctx.next(5).failure("Hello".to_string());
-    assert_eq!(ctx.ctx.text, "Hello, world!");
+    assert_eq!(&*ctx.ctx.text, "Hello, world!");
     assert_eq!(ctx.ctx.index, 5);
-    assert_eq!(ctx.expected, "Hello");
+    assert_eq!(ctx.expected, vec!["Hello"]);
   }
 
   #[test]
@@ -256,4 +833,104 @@ mod ctx_test {
     assert_eq!(failure.ctx, ctx);
     assert_eq!(failure.expected(), "expected space".to_string());
   }
+
+  #[test]
+  fn test_line_and_column() {
+    let ctx = Ctx::new("Foo(\n  1,\n  bär)");
+    assert_eq!((ctx.line(), ctx.column()), (1, 1));
+
+    let ctx = ctx.skip(4);
+    assert_eq!((ctx.line(), ctx.column()), (1, 5));
+
+    let ctx = ctx.skip(1);
+    assert_eq!((ctx.line(), ctx.column()), (2, 1));
+
+    let ctx = ctx.skip(2);
+    assert_eq!((ctx.line(), ctx.column()), (2, 3));
+
+    // columns count chars, not bytes
+    let ctx = Ctx::new("Foo(\n  1,\n  bär)").skip(16);
+    assert_eq!(ctx.text_slice(), ")");
+    assert_eq!((ctx.line(), ctx.column()), (3, 6));
+
+    // `ä` takes bytes 13 and 14, past the end is the last position
+    assert_eq!(line_col("Foo(\n  1,\n  bär)", 14), (3, 4));
+    assert_eq!(line_col("Foo(\n  1,\n  bär)", 99), (3, 7));
+  }
+
+  #[test]
+  fn test_failure_position() {
+    let ctx = Ctx::new("Foo(\n  1,\n  bar baz)");
+    let failure = ctx.skip(12).parse_str("1").unwrap_err();
+    assert_eq!(failure.index(), 12);
+    assert_eq!(failure.line(), 3);
+    assert_eq!(failure.column(), 3);
+    assert_eq!(failure.span(), Span::new(12, 15));
+
+    let failure = ctx.skip(15).parse_str(")").unwrap_err();
+    assert_eq!(failure.span(), Span::new(15, 16));
+
+    let failure = ctx.skip(100).parse_str(")").unwrap_err();
+    assert_eq!(failure.span(), Span::new(20, 20));
+  }
+
+  #[test]
+  fn test_success_map_keeps_span() {
+    let ctx = Ctx::new("Hello, world!").skip(7);
+    let success = ctx.parse_str("world").unwrap();
+    let success = success.map(|val| val.len());
+    assert_eq!(success.val(), 5);
+    assert_eq!(success.span(), Span::new(7, 12));
+  }
+
+  #[test]
+  fn test_failure_merge() {
+    let ctx = Ctx::new("Hello, world!");
+    let failure = ctx
+      .failure("number".to_string())
+      .merge(ctx.failure("boolean".to_string()))
+      .merge(ctx.failure("number".to_string()));
+    assert_eq!(failure.expected_set(), ["number", "boolean"]);
+    assert_eq!(failure.expected(), "one of: number, boolean");
+
+    // the furthest failure wins
+    let further = ctx.skip(2).failure("identifier".to_string());
+    let failure = failure.merge(further.clone());
+    assert_eq!(failure, further);
+    let failure = failure.merge(ctx.failure("number".to_string()));
+    assert_eq!(failure, further);
+  }
+
+  #[test]
+  fn test_failure_commit() {
+    let ctx = Ctx::new("Hello, world!");
+    let failure = ctx.failure("number".to_string());
+    assert!(!failure.is_committed());
+
+    let failure = failure.commit();
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), "number");
+    assert_eq!(failure.index(), 0);
+
+    let failure = failure.relabel("boolean".to_string());
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), "boolean");
+  }
+
+  #[test]
+  fn test_record() {
+    let ctx = Ctx::new("Hello, world!").with_recovery();
+    assert!(ctx.is_recovering());
+    assert!(ctx.skip(2).is_recovering());
+
+    let first = Diagnostic::new("first", Span::new(0, 1));
+    let second = Diagnostic::new("second", Span::new(1, 2));
+    let recorded = ctx.record(first.clone()).skip(3).record(second.clone());
+    assert_eq!(recorded.diagnostics(), vec![first.clone(), second]);
+    assert_eq!(recorded.index(), 3);
+
+    // other paths from the same ctx are not affected
+    assert_eq!(ctx.diagnostics(), vec![]);
+    assert_eq!(ctx.record(first.clone()).diagnostics(), vec![first]);
+  }
 }
@@ -1,3 +1,9 @@
-mod combinator;
-mod foundation;
+pub mod combinator;
+pub mod diagnostics;
+pub mod eval;
+pub mod foundation;
 pub mod parser;
+pub mod trace;
+
+pub use call_ast::{printer, visit};
+pub use combinator::Parser;
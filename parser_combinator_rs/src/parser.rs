@@ -1,119 +1,328 @@
 use crate::{
-  combinator::{any, delimited, separated, ParserFn},
-  foundation::{Ctx, Result},
+  combinator::{
+    any, cut, delimited, delimited_left, lexeme, many, named, precedence, recognize, recover,
+    regex, satisfy, trivia, Assoc, Operator, Parser, ParserFn,
+  },
+  diagnostics::Diagnostic,
+  foundation::{Ctx, Failure, Regex, Result},
+  trace::Trace,
 };
+use call_ast::{Backend, ParseError, BINARY_OPERATORS, TRIVIA, UNARY_OPERATORS};
+use std::sync::LazyLock;
+use unicode_ident::{is_xid_continue, is_xid_start};
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct Call {
-  target: String,
-  args: Vec<Expr>,
-}
+pub use call_ast::{BinOp, Call, Expr, UnaryOp};
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum Expr {
-  Num(i32),
-  Bool(bool),
-  Call(Call),
-}
+// compiled on first use, `regex` matches them at the current position only
+static INTEGER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[+\-]?[0-9]+").unwrap());
+static FLOAT: LazyLock<Regex> = LazyLock::new(|| {
+  Regex::new(r"[+\-]?[0-9]+(\.[0-9]*([eE][+\-]?[0-9]+)?|[eE][+\-]?[0-9]+)").unwrap()
+});
+static UNICODE_ESCAPE: LazyLock<Regex> =
+  LazyLock::new(|| Regex::new(r"\\u\{(?P<hex>[0-9a-fA-F]{1,6})\}").unwrap());
 
 // our top level parsing function that takes care of creating a `Ctx`, and unboxing the final AST (or throwing)
 pub fn parse(code: &str) -> std::result::Result<Expr, String> {
   let ctx = Ctx::new(code);
-  let res = expr(&ctx);
-  let success = res.map_err(|f| {
-    format!(
-      "Parse error, expected {} at char {}",
-      f.expected(),
-      f.index()
-    )
-  })?;
-  Ok(success.val())
-}
-
-// expr = call | number_literal | boolean_literal;
+  let success = program(&ctx).map_err(|f| error_message(&f))?;
+  Ok(success.into_val())
+}
+
+// like `parse` but also gives the trace of the grammar rules, each one with
+// where it started and whether it matched or backtracked
+pub fn parse_traced(code: &str) -> (std::result::Result<Expr, String>, Trace) {
+  let ctx = Ctx::new(code).with_trace();
+  let result = program(&ctx)
+    .map(|success| success.into_val())
+    .map_err(|f| error_message(&f));
+  (result, ctx.trace().unwrap())
+}
+
+fn error_message(failure: &Failure) -> String {
+  format!(
+    "Parse error, expected {} at line {} column {}",
+    failure.expected(),
+    failure.line(),
+    failure.column()
+  )
+}
+
+// like `parse` but keeps the failure position, ready to be rendered against `code`
+pub fn parse_with_diagnostic(code: &str) -> std::result::Result<Expr, Diagnostic> {
+  let ctx = Ctx::new(code);
+  let success = program(&ctx).map_err(Diagnostic::from)?;
+  Ok(success.into_val())
+}
+
+// `parse` behind the shared `Backend` interface, the error offset is the
+// furthest position any alternative reached
+pub struct CombinatorBackend;
+
+impl Backend for CombinatorBackend {
+  fn name(&self) -> &str {
+    "combinator"
+  }
+
+  fn parse(&self, code: &str) -> std::result::Result<Expr, ParseError> {
+    let ctx = Ctx::new(code);
+    match program(&ctx) {
+      Ok(success) => Ok(success.into_val()),
+      Err(failure) => Err(ParseError::new(
+        format!("expected {}", failure.expected()),
+        failure.index(),
+      )),
+    }
+  }
+}
+
+// keeps going after syntax errors inside argument lists, every error is
+// reported and the broken argument is replaced by `Expr::Error`
+pub fn parse_with_recovery(code: &str) -> (Option<Expr>, Vec<Diagnostic>) {
+  let ctx = Ctx::new(code).with_recovery();
+  match program(&ctx) {
+    Ok(success) => {
+      let (ctx, val) = success.into_parts();
+      let mut diagnostics = ctx.diagnostics();
+      if !ctx.text_slice().is_empty() {
+        let expected = ["end of input".to_string()];
+        diagnostics.push(Diagnostic::unexpected(code, ctx.token_span()).with_expected(expected));
+      }
+      (Some(val), diagnostics)
+    }
+    Err(failure) => {
+      let mut diagnostics = failure.ctx().diagnostics();
+      diagnostics.push(Diagnostic::from(&failure));
+      (None, diagnostics)
+    }
+  }
+}
+
+// program = trivia expr
+fn program(ctx: &Ctx) -> Result<Expr> {
+  named("program", delimited_left(trivia(TRIVIA), expr))(ctx)
+}
+
+// a token followed by any trivia
+fn token<T>(parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<T> {
+  lexeme(TRIVIA, parser)
+}
+
+fn punct(punct: &'static str) -> impl Fn(&Ctx) -> Result<String> {
+  token(move |ctx: &Ctx| ctx.parse_str(punct))
+}
+
+// expr = unary ( binary_op unary ) *
+// unary = primary | unary_op unary
+// see `BINARY_OPERATORS` and `UNARY_OPERATORS` for the precedences
 fn expr(ctx: &Ctx) -> Result<Expr> {
-  // [call, numberLiteral]
+  EXPR.with(|expr| expr(ctx))
+}
+
+thread_local! {
+  // the operator table is built once per thread, `expr` runs for every
+  // argument and parenthesised group
+  static EXPR: ParserFn<Expr> = {
+    let binary = BINARY_OPERATORS.map(|(token, precedence, op)| {
+      Operator::infix(punct(token), precedence, Assoc::Left, move |lhs, rhs| {
+        Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+      })
+    });
+    let unary = UNARY_OPERATORS.map(|(token, precedence, op)| {
+      Operator::prefix(punct(token), precedence, move |val| {
+        Expr::Unary(op, Box::new(val))
+      })
+    });
+    let table = binary.into_iter().chain(unary).collect();
+    Box::new(named("expr", precedence(primary, table)))
+  };
+}
+
+// primary = boolean_literal | null_literal | float_literal | number_literal | string_literal | call | "(" expr ")"
+fn primary(ctx: &Ctx) -> Result<Expr> {
   let parsers: Vec<ParserFn<Expr>> = vec![
-    // bool literal
-    Box::new(|ctx| {
-      let target = bool_literal(ctx)?;
-      let val = target.val();
-      Ok(target.ctx().success(Expr::Bool(val)))
-    }),
-    // number literal
-    Box::new(|ctx| {
-      let target = number_literal(ctx)?;
-      let val = target.val();
-      Ok(target.ctx().success(Expr::Num(val)))
-    }),
-    // call
-    Box::new(|ctx| {
-      let target = call(ctx)?;
-      let val = target.val();
-      Ok(target.ctx().success(Expr::Call(val)))
-    }),
+    Box::new(
+      token(keyword(bool_literal))
+        .label("boolean")
+        .map(Expr::Bool)
+        .named("boolean"),
+    ),
+    Box::new(
+      token(keyword(null_literal))
+        .label("null")
+        .map(|_| Expr::Null)
+        .named("null"),
+    ),
+    Box::new(token(float_literal).map(Expr::Float).named("float")),
+    Box::new(token(number_literal).map(Expr::Num).named("number")),
+    Box::new(token(string_literal).map(Expr::Str).named("string")),
+    Box::new(call.map(Expr::Call).named("call")),
+    Box::new(delimited(punct("("), cut(expr), cut(punct(")"))).named("group")),
   ];
-  any::<Expr>(parsers)(ctx)
+  any(parsers)(ctx)
+}
+
+// a word that is not the start of a longer identifier, `nullable()` is a call
+fn keyword<T>(parser: impl Fn(&Ctx) -> Result<T>) -> impl Fn(&Ctx) -> Result<T> {
+  move |ctx| {
+    let success = parser(ctx)?;
+    let rest = success.ctx().text_slice();
+    if rest.starts_with(is_xid_continue) {
+      return Err(ctx.failure("keyword".to_owned()));
+    }
+    Ok(success)
+  }
 }
 
-// our regexp to match identifiers
+// ident = ( XID_Start | "_" ) XID_Continue *, Unicode identifiers as in Rust
 fn ident(ctx: &Ctx) -> Result<String> {
-  let re = regex::Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*").unwrap();
-  ctx.parse_regex(re, "identifier".to_owned())
+  let start = satisfy(|c| c == '_' || is_xid_start(c), "identifier");
+  recognize(start.then(many(satisfy(is_xid_continue, "identifier"))))(ctx)
 }
 
-// a regexp parser to match a number string
-fn number_literal(ctx: &Ctx) -> Result<i32> {
-  let re = regex::Regex::new(r"^[+\-]?[0-9]+(\.[0-9]*)?").unwrap();
-  let success = ctx.parse_regex(re, "number".to_owned())?;
-  let result = success.val().parse();
+// a regexp parser to match an integer string, it must fit in 64 bits
+fn number_literal(ctx: &Ctx) -> Result<i64> {
+  let success = regex(&INTEGER, "number")(ctx)?;
+  let result = success.val().as_str().parse();
   match result {
-    Ok(num) => Ok(success.ctx().success(num)),
-    Err(_) => Err(success.ctx().failure("number".to_owned())),
+    Ok(num) => Ok(success.map(|_| num)),
+    Err(_) => Err(ctx.failure("64-bit integer".to_owned()).commit()),
   }
 }
 
+// a number with a fraction, an exponent or both
+fn float_literal(ctx: &Ctx) -> Result<f64> {
+  let success = regex(&FLOAT, "number")(ctx)?;
+  let result: std::result::Result<f64, _> = success.val().as_str().parse();
+  match result {
+    Ok(num) if num.is_finite() => Ok(success.map(|_| num)),
+    _ => Err(ctx.failure("finite number".to_owned()).commit()),
+  }
+}
+
+// "..." with the escapes \n \r \t \0 \\ \" and \u{XXXX}
+fn string_literal(ctx: &Ctx) -> Result<String> {
+  let open = ctx
+    .parse_str("\"")
+    .map_err(|f| f.relabel("string".to_owned()))?;
+  let mut next_ctx = open.into_parts().0;
+  let mut text = String::new();
+  loop {
+    let c = match next_ctx.text_slice().chars().next() {
+      Some('"') => return Ok(next_ctx.skip(1).success_from(ctx.index(), text)),
+      Some('\\') => {
+        let (ctx, c) = escape(&next_ctx)?.into_parts();
+        next_ctx = ctx;
+        text.push(c);
+        continue;
+      }
+      Some(c) => c,
+      None => return Err(next_ctx.failure("\"".to_owned()).commit()),
+    };
+    next_ctx = next_ctx.skip(c.len_utf8());
+    text.push(c);
+  }
+}
+
+fn escape(ctx: &Ctx) -> Result<char> {
+  let invalid = || ctx.failure("escape sequence".to_owned()).commit();
+  let c = match ctx.text_slice().chars().nth(1) {
+    Some('n') => '\n',
+    Some('r') => '\r',
+    Some('t') => '\t',
+    Some('0') => '\0',
+    Some('\\') => '\\',
+    Some('"') => '"',
+    Some('u') => {
+      let success = regex(&UNICODE_ESCAPE, "escape sequence")(ctx).map_err(|_| invalid())?;
+      let escape = success.val();
+      let code = u32::from_str_radix(escape.name("hex").unwrap(), 16).unwrap();
+      return match char::from_u32(code) {
+        Some(c) => Ok(success.map(|_| c)),
+        None => Err(invalid()),
+      };
+    }
+    _ => return Err(invalid()),
+  };
+  Ok(ctx.skip(2).success_from(ctx.index(), c))
+}
+
+fn null_literal(ctx: &Ctx) -> Result<()> {
+  Ok(ctx.parse_str("null")?.map(|_| ()))
+}
+
 fn bool_literal(ctx: &Ctx) -> Result<bool> {
-  any(vec![
-    Box::new(|ctx| {
-      let target = ctx.parse_str("true".to_string())?;
-      Ok(target.ctx().success(true))
-    }),
-    Box::new(|ctx| {
-      let target = ctx.parse_str("false".to_string())?;
-      Ok(target.ctx().success(false))
-    }),
-  ])(ctx)
-}
-
-// args = expr ( trailingArg ) *
+  let r#true = |ctx: &Ctx| ctx.parse_str("true");
+  let r#false = |ctx: &Ctx| ctx.parse_str("false");
+  r#true.map(|_| true).or(r#false.map(|_| false)).parse(ctx)
+}
+
+// args = ( expr ( "," expr ) * ) ?
 fn args(ctx: &Ctx) -> Result<Vec<Expr>> {
-  let comma = Box::new(|ctx: &Ctx| ctx.parse_str(",".to_owned()));
-  let parser = separated(comma, expr);
-  parser(ctx)
+  let comma = punct(",");
+  // there may be no arguments, but after `(` anything other than `)` has to
+  // be one, so a broken first argument is committed and can be recovered
+  let first_arg = |ctx: &Ctx| match expr(ctx) {
+    Err(failure) if !failure.is_committed() && !ctx.text_slice().starts_with(')') => {
+      Err(failure.merge(ctx.failure(")".to_owned())).commit())
+    }
+    res => res,
+  };
+  let arg = recover(first_arg, skip_arg, || Expr::Error);
+  let trailing_arg = delimited_left(comma, recover(cut(expr), skip_arg, || Expr::Error));
+  arg
+    .then(many(trailing_arg))
+    .optional()
+    .map(|args| match args {
+      Some((head, tail)) => [vec![head], tail].concat(),
+      None => vec![],
+    })
+    .named("args")
+    .parse(ctx)
+}
+
+// skips a broken argument up to the next `,` or `)` outside of nested
+// parenthesis and strings
+fn skip_arg(ctx: &Ctx) -> Result<()> {
+  let mut depth = 0;
+  let mut in_string = false;
+  let mut escaped = false;
+  let mut len = 0;
+  for c in ctx.text_slice().chars() {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' if in_string => escaped = true,
+      '"' => in_string = !in_string,
+      _ if in_string => {}
+      ',' | ')' if depth == 0 => break,
+      '(' => depth += 1,
+      ')' => depth -= 1,
+      _ => {}
+    }
+    len += c.len_utf8();
+  }
+  Ok(ctx.skip(len).success_from(ctx.index(), ()))
 }
 
 // call = ident "(" args ")"
+// an identifier is only ever a call target, so any failure after it is committed
 fn call(ctx: &Ctx) -> Result<Call> {
-  let success = ident(ctx)?;
-  let target = success.val();
-
-  let delimited_args = delimited(
-    |ctx| ctx.parse_str("(".to_string()),
-    args,
-    |ctx| ctx.parse_str(")".to_string()),
-  );
-
-  let success = delimited_args(success.ctx())?;
-  let args = success.val();
+  let delimited_args = delimited(punct("("), args, punct(")"));
 
-  Ok(success.ctx().success(Call { target, args }))
+  token(ident)
+    .then(cut(delimited_args))
+    .map(|(target, args)| Call::new(target, args))
+    .parse(ctx)
 }
 
 #[cfg(test)]
 mod test {
   use super::*;
-  use crate::foundation::Ctx;
+  use crate::{
+    foundation::{Ctx, Span},
+    trace::Outcome,
+  };
+  use call_ast::{printer::pretty, strategy::arb_expr};
+  use proptest::prelude::*;
 
   #[test]
   fn test_bool_literal() {
@@ -134,6 +343,369 @@ mod test {
     assert!(res.is_err());
   }
 
+  #[test]
+  fn test_expr_expected() {
+    let failure = expr(&Ctx::new("@")).unwrap_err();
+    assert_eq!(failure.index(), 0);
+    assert_eq!(
+      failure.expected(),
+      "one of: boolean, null, number, string, identifier, (, -, !"
+    );
+
+    let err = parse("@").unwrap_err();
+    assert_eq!(
+      err,
+      "Parse error, expected one of: boolean, null, number, string, identifier, (, -, ! at line 1 column 1"
+    );
+  }
+
+  #[test]
+  fn test_call_committed() {
+    let err = parse("Foo(1,@)").unwrap_err();
+    assert_eq!(
+      err,
+      "Parse error, expected one of: boolean, null, number, string, identifier, (, -, ! at line 1 column 7"
+    );
+
+    let failure = call(&Ctx::new("Foo(Bar(1,2;true),false)")).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), ")");
+    assert_eq!(failure.index(), 11);
+
+    let failure = call(&Ctx::new("Foo(,)")).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.index(), 4);
+
+    // an identifier must be followed by the arguments
+    let failure = call(&Ctx::new("Foo")).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), "(");
+    assert_eq!(failure.index(), 3);
+
+    let failure = call(&Ctx::new("1")).unwrap_err();
+    assert!(!failure.is_committed());
+  }
+
+  #[test]
+  fn test_parse_with_recovery() {
+    let (expr, diagnostics) = parse_with_recovery("Foo(1,2)");
+    assert_eq!(
+      expr,
+      Some(Expr::Call(Call::new(
+        "Foo",
+        vec![Expr::Num(1), Expr::Num(2)]
+      )))
+    );
+    assert_eq!(diagnostics, vec![]);
+
+    let (expr, diagnostics) = parse_with_recovery("Foo(1,@,Bar(true,),3)");
+    assert_eq!(
+      expr,
+      Some(Expr::Call(Call::new(
+        "Foo",
+        vec![
+          Expr::Num(1),
+          Expr::Error,
+          Expr::Call(Call::new("Bar", vec![Expr::Bool(true), Expr::Error])),
+          Expr::Num(3)
+        ]
+      )))
+    );
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].span(), Span::new(6, 7));
+    assert_eq!(
+      diagnostics[0].expected(),
+      [
+        "boolean",
+        "null",
+        "number",
+        "string",
+        "identifier",
+        "(",
+        "-",
+        "!"
+      ]
+    );
+    assert_eq!(diagnostics[1].span(), Span::new(17, 18));
+
+    // errors inside nested calls are recovered by the enclosing argument list
+    let (expr, diagnostics) = parse_with_recovery("Foo(Bar(1;2),3)");
+    assert_eq!(
+      expr,
+      Some(Expr::Call(Call::new(
+        "Foo",
+        vec![Expr::Error, Expr::Num(3)]
+      )))
+    );
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span(), Span::new(9, 10));
+    assert_eq!(diagnostics[0].expected(), [")"]);
+
+    // the first argument too
+    let (expr, diagnostics) = parse_with_recovery("Foo(@, 1)");
+    assert_eq!(
+      expr,
+      Some(Expr::Call(Call::new(
+        "Foo",
+        vec![Expr::Error, Expr::Num(1)]
+      )))
+    );
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span(), Span::new(4, 5));
+    assert_eq!(diagnostics[0].expected().last().unwrap(), ")");
+
+    let (expr, diagnostics) = parse_with_recovery("Foo()");
+    assert_eq!(expr, Some(Expr::Call(Call::new("Foo", vec![]))));
+    assert_eq!(diagnostics, vec![]);
+
+    let (expr, diagnostics) = parse_with_recovery("Foo(1)2");
+    assert!(expr.is_some());
+    assert_eq!(diagnostics[0].expected(), ["end of input"]);
+
+    let (expr, diagnostics) = parse_with_recovery("@");
+    assert_eq!(expr, None);
+    assert_eq!(diagnostics.len(), 1);
+  }
+
+  #[test]
+  fn test_number_literal() {
+    assert_eq!(parse("-12"), Ok(Expr::Num(-12)));
+    assert_eq!(
+      parse("9223372036854775807"),
+      Ok(Expr::Num(9223372036854775807))
+    );
+    assert_eq!(
+      parse("-9223372036854775808"),
+      Ok(Expr::Num(-9223372036854775808))
+    );
+
+    let failure = expr(&Ctx::new("Foo(1, 9223372036854775808)").skip(7)).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), "64-bit integer");
+    assert_eq!(failure.index(), 7);
+
+    let diagnostic = parse_with_diagnostic("Foo(1, 9223372036854775808)").unwrap_err();
+    assert_eq!(diagnostic.message(), "unexpected `9223372036854775808`");
+    assert_eq!(diagnostic.span(), Span::new(7, 26));
+  }
+
+  #[test]
+  fn test_float_literal() {
+    assert_eq!(parse("1.5"), Ok(Expr::Float(1.5)));
+    assert_eq!(parse("-0.25"), Ok(Expr::Float(-0.25)));
+    assert_eq!(parse("1."), Ok(Expr::Float(1.0)));
+    assert_eq!(parse("+2e3"), Ok(Expr::Float(2000.0)));
+    assert_eq!(parse("1.5E-1"), Ok(Expr::Float(0.15)));
+
+    let failure = float_literal(&Ctx::new("1e999")).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), "finite number");
+
+    // plain integers are left to `number_literal`
+    assert!(float_literal(&Ctx::new("12")).is_err());
+  }
+
+  #[test]
+  fn test_string_literal() {
+    assert_eq!(parse(r#""""#), Ok(Expr::Str("".to_string())));
+    assert_eq!(
+      parse(r#""hola, \"mundo\")""#),
+      Ok(Expr::Str("hola, \"mundo\")".to_string()))
+    );
+    assert_eq!(
+      parse(r#""a\nb\tc\\d\0""#),
+      Ok(Expr::Str("a\nb\tc\\d\0".to_string()))
+    );
+    assert_eq!(
+      parse(r#""\u{48}\u{1F600} ñ""#),
+      Ok(Expr::Str("H\u{1F600} ñ".to_string()))
+    );
+
+    let failure = string_literal(&Ctx::new(r#""abc\q""#)).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), "escape sequence");
+    assert_eq!(failure.index(), 4);
+
+    let failure = string_literal(&Ctx::new(r#""\u{D800}""#)).unwrap_err();
+    assert_eq!(failure.expected(), "escape sequence");
+
+    let failure = string_literal(&Ctx::new(r#""abc"#)).unwrap_err();
+    assert!(failure.is_committed());
+    assert_eq!(failure.expected(), "\"");
+    assert_eq!(failure.index(), 4);
+  }
+
+  #[test]
+  fn test_literals_in_call() {
+    assert_eq!(
+      parse(r#"Foo(null, 1.5, "a,b)", 2)"#),
+      Ok(Expr::Call(Call::new(
+        "Foo",
+        vec![
+          Expr::Null,
+          Expr::Float(1.5),
+          Expr::Str("a,b)".to_string()),
+          Expr::Num(2)
+        ]
+      )))
+    );
+
+    let (expr, diagnostics) = parse_with_recovery(r#"Foo("a\q,)", 2)"#);
+    assert_eq!(
+      expr,
+      Some(Expr::Call(Call::new(
+        "Foo",
+        vec![Expr::Error, Expr::Num(2)]
+      )))
+    );
+    assert_eq!(diagnostics.len(), 1);
+  }
+
+  #[test]
+  fn test_operators() {
+    let num = |n| Box::new(Expr::Num(n));
+    let binary = |op, lhs, rhs| Box::new(Expr::Binary(op, lhs, rhs));
+
+    assert_eq!(
+      parse("1 + 2 * Foo(3)"),
+      Ok(*binary(
+        BinOp::Add,
+        num(1),
+        binary(
+          BinOp::Mul,
+          num(2),
+          Box::new(Expr::Call(Call::new("Foo", vec![Expr::Num(3)])))
+        )
+      ))
+    );
+    assert_eq!(
+      parse("(1 + 2) * 3"),
+      Ok(*binary(
+        BinOp::Mul,
+        binary(BinOp::Add, num(1), num(2)),
+        num(3)
+      ))
+    );
+    assert_eq!(
+      parse("1 - 2 - 3"),
+      Ok(*binary(
+        BinOp::Sub,
+        binary(BinOp::Sub, num(1), num(2)),
+        num(3)
+      ))
+    );
+    assert_eq!(
+      parse("1 <= 2 && 3 != 4"),
+      Ok(*binary(
+        BinOp::And,
+        binary(BinOp::Le, num(1), num(2)),
+        binary(BinOp::Ne, num(3), num(4))
+      ))
+    );
+
+    // a sign right before a number is part of the literal
+    assert_eq!(parse("-1"), Ok(Expr::Num(-1)));
+    assert_eq!(parse("1-2"), Ok(*binary(BinOp::Sub, num(1), num(2))));
+    assert_eq!(
+      parse("-Foo() * !true"),
+      Ok(*binary(
+        BinOp::Mul,
+        Box::new(Expr::Unary(
+          UnaryOp::Neg,
+          Box::new(Expr::Call(Call::new("Foo", vec![])))
+        )),
+        Box::new(Expr::Unary(UnaryOp::Not, Box::new(Expr::Bool(true))))
+      ))
+    );
+    assert_eq!(
+      parse("Foo(1 + 2, 3)"),
+      Ok(Expr::Call(Call::new(
+        "Foo",
+        vec![*binary(BinOp::Add, num(1), num(2)), Expr::Num(3)]
+      )))
+    );
+
+    let err = parse("1 + ").unwrap_err();
+    assert_eq!(
+      err,
+      "Parse error, expected one of: boolean, null, number, string, identifier, (, -, ! at line 1 column 5"
+    );
+    let err = parse("(1 + 2").unwrap_err();
+    assert_eq!(err, "Parse error, expected ) at line 1 column 7");
+
+    let (expr, diagnostics) = parse_with_recovery("Foo(1 *, 2)");
+    assert_eq!(
+      expr,
+      Some(Expr::Call(Call::new(
+        "Foo",
+        vec![Expr::Error, Expr::Num(2)]
+      )))
+    );
+    assert_eq!(diagnostics[0].span(), Span::new(7, 8));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde() {
+    let expr = parse(r#"Foo(1, -2.5, "a\n", null, !true || Bar() < 3)"#).unwrap();
+    let json = serde_json::to_string(&expr).unwrap();
+    assert_eq!(serde_json::from_str::<Expr>(&json).unwrap(), expr);
+
+    let json = serde_json::to_string(&parse("Foo(1 + 2)").unwrap()).unwrap();
+    assert_eq!(
+      json,
+      r#"{"Call":{"target":"Foo","args":[{"Binary":["Add",{"Num":1},{"Num":2}]}]}}"#
+    );
+  }
+
+  #[test]
+  fn test_trivia() {
+    let expected = Expr::Call(Call::new("Foo", vec![Expr::Num(1), Expr::Num(2)]));
+    assert_eq!(parse("Foo( 1, 2 )"), Ok(expected.clone()));
+    assert_eq!(parse("  Foo (\n  1 ,\n  2\n)\n"), Ok(expected.clone()));
+    assert_eq!(
+      parse("// call\nFoo(1, /* two */ 2) // done"),
+      Ok(expected.clone())
+    );
+
+    let err = parse("Foo(\n  1,\n  @\n)").unwrap_err();
+    assert_eq!(
+      err,
+      "Parse error, expected one of: boolean, null, number, string, identifier, (, -, ! at line 3 column 3"
+    );
+
+    let err = parse("Foo(1 /* 2) ").unwrap_err();
+    assert_eq!(err, "Parse error, expected */ at line 1 column 13");
+  }
+
+  #[test]
+  fn test_keyword() {
+    assert_eq!(
+      parse("nullable()"),
+      Ok(Expr::Call(Call::new("nullable", vec![])))
+    );
+    // also as arguments, where a keyword prefix used to leave `able()` behind
+    assert_eq!(
+      parse("Foo(nullable(), trueish(), false_())"),
+      Ok(Expr::Call(Call::new(
+        "Foo",
+        vec![
+          Expr::Call(Call::new("nullable", vec![])),
+          Expr::Call(Call::new("trueish", vec![])),
+          Expr::Call(Call::new("false_", vec![])),
+        ]
+      )))
+    );
+    assert_eq!(
+      parse("true && false"),
+      Ok(Expr::Binary(
+        BinOp::And,
+        Box::new(Expr::Bool(true)),
+        Box::new(Expr::Bool(false))
+      ))
+    );
+  }
+
   #[test]
   fn test_ident() {
     let ctx = Ctx::new("foo");
@@ -151,6 +723,16 @@ mod test {
     assert_eq!(res.val(), "foo");
     assert_eq!(res.index(), 3);
     assert_eq!(res.ctx().text_slice(), "(");
+
+    let res = ident(&Ctx::new("_ñandú2 = 1")).unwrap();
+    assert_eq!(res.val(), "_ñandú2");
+    assert_eq!(res.ctx().text_slice(), " = 1");
+    assert_eq!(ident(&Ctx::new("名前(")).unwrap().val(), "名前");
+    // combining marks continue an identifier but do not start one
+    assert_eq!(ident(&Ctx::new("e\u{301}")).unwrap().index(), 3);
+    assert!(ident(&Ctx::new("\u{301}e")).is_err());
+    assert!(ident(&Ctx::new("2a")).is_err());
+    assert!(ident(&Ctx::new("€")).is_err());
   }
 
   #[test]
@@ -158,21 +740,79 @@ mod test {
     let ctx = Ctx::new("foo()");
     let success = call(&ctx).unwrap();
     assert_eq!(success.index(), 5);
-    assert_eq!(success.val().target, "foo");
-    assert_eq!(success.val().args.len(), 0);
+    assert_eq!(success.val().target(), "foo");
+    assert_eq!(success.val().args().len(), 0);
 
     let ctx = Ctx::new("Foo(Bar(1,2,true),false)");
     let success = call(&ctx).unwrap();
     assert_eq!(success.index(), 24);
-    assert_eq!(success.val().target, "Foo");
-    assert_eq!(success.val().args.len(), 2);
+    assert_eq!(success.span(), Span::new(0, 24));
+    assert_eq!(success.val().target(), "Foo");
+    assert_eq!(success.val().args().len(), 2);
     assert_eq!(
-      success.val().args[0],
-      Expr::Call(Call {
-        target: "Bar".to_string(),
-        args: vec![Expr::Num(1), Expr::Num(2), Expr::Bool(true)]
-      })
+      success.val().args()[0],
+      Expr::Call(Call::new(
+        "Bar",
+        vec![Expr::Num(1), Expr::Num(2), Expr::Bool(true)]
+      ))
     );
-    assert_eq!(success.val().args[1], Expr::Bool(false));
+    assert_eq!(success.val().args()[1], Expr::Bool(false));
+  }
+
+  #[test]
+  fn test_parse_traced() {
+    let (result, trace) = parse_traced("f(1)");
+    assert_eq!(result, parse("f(1)"));
+    let expected = [
+      "program 0..4 ok \"f(1)\"",
+      "  expr 0..4 ok \"f(1)\"",
+      "    boolean 0..0 backtrack, expected boolean",
+      "    null 0..0 backtrack, expected null",
+      "    float 0..0 backtrack, expected number",
+      "    number 0..0 backtrack, expected number",
+      "    string 0..0 backtrack, expected string",
+      "    call 0..4 ok \"f(1)\"",
+      "      args 2..3 ok \"1\"",
+      "        expr 2..3 ok \"1\"",
+      "          boolean 2..2 backtrack, expected boolean",
+      "          null 2..2 backtrack, expected null",
+      "          float 2..2 backtrack, expected number",
+      "          number 2..3 ok \"1\"",
+    ];
+    assert_eq!(trace.to_text(), expected.join("\n") + "\n");
+
+    // the call commits after its target, so `any` tries nothing after it
+    let (result, trace) = parse_traced("f[1]");
+    assert!(result.is_err());
+    let names: Vec<_> = trace.walk().map(|node| node.name.as_str()).collect();
+    assert_eq!(names.last(), Some(&"call"));
+    let call = trace.walk().find(|node| node.name == "call").unwrap();
+    assert_eq!(
+      call.outcome,
+      Outcome::Failure {
+        index: 1,
+        expected: vec!["(".to_owned()],
+        committed: true
+      }
+    );
+  }
+
+  #[test]
+  fn test_backend() {
+    assert_eq!(CombinatorBackend.parse("Foo(1)").ok(), parse("Foo(1)").ok());
+    let err = CombinatorBackend.parse("Foo(1, @)").unwrap_err();
+    assert_eq!(err.offset, 7);
+    assert_eq!(
+      err.to_string(),
+      "expected one of: boolean, null, number, string, identifier, (, -, ! at offset 7"
+    );
+  }
+
+  proptest! {
+    #[test]
+    fn test_round_trip(expr in arb_expr(), width in 0usize..60) {
+      prop_assert_eq!(parse(&expr.to_string()), Ok(expr.clone()));
+      prop_assert_eq!(parse(&pretty(&expr, width)), Ok(expr));
+    }
   }
 }
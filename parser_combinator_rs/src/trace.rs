@@ -0,0 +1,267 @@
+use crate::foundation::Span;
+use std::{cell::RefCell, fmt, rc::Rc};
+
+// What happened to one run of a named parser, see `combinator::named`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+  Success(Span),
+  // `committed` is a cut, otherwise the caller is free to backtrack
+  Failure {
+    index: usize,
+    expected: Vec<String>,
+    committed: bool,
+  },
+}
+
+// A run of a named parser with the named parsers it ran in turn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceNode {
+  pub name: String,
+  pub start: usize,
+  pub outcome: Outcome,
+  pub children: Vec<TraceNode>,
+}
+
+impl TraceNode {
+  pub fn is_success(&self) -> bool {
+    matches!(self.outcome, Outcome::Success(_))
+  }
+
+  // the parser and its children, depth first
+  pub fn walk(&self) -> impl Iterator<Item = &TraceNode> {
+    let mut stack = vec![self];
+    std::iter::from_fn(move || {
+      let node = stack.pop()?;
+      stack.extend(node.children.iter().rev());
+      Some(node)
+    })
+  }
+
+  fn summary(&self, text: &str) -> String {
+    match &self.outcome {
+      Outcome::Success(span) => {
+        format!(
+          "{} {}..{} ok {:?}",
+          self.name,
+          span.start,
+          span.end,
+          &text[span.start..span.end]
+        )
+      }
+      Outcome::Failure {
+        index,
+        expected,
+        committed,
+      } => {
+        let failed = if *committed { "cut" } else { "backtrack" };
+        let expected = expected.join(", ");
+        format!(
+          "{} {}..{index} {failed}, expected {expected}",
+          self.name, self.start
+        )
+      }
+    }
+  }
+}
+
+// The named parsers run by one parse, in the order they were entered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+  text: Rc<str>,
+  roots: Vec<TraceNode>,
+}
+
+impl Trace {
+  pub fn roots(&self) -> &[TraceNode] {
+    &self.roots
+  }
+
+  pub fn walk(&self) -> impl Iterator<Item = &TraceNode> {
+    self.roots.iter().flat_map(TraceNode::walk)
+  }
+
+  // one line per parser, children indented under their parent
+  pub fn to_text(&self) -> String {
+    let mut out = String::new();
+    for (node, depth) in self.with_depth() {
+      out += &format!("{}{}\n", "  ".repeat(depth), node.summary(&self.text));
+    }
+    out
+  }
+
+  // a Graphviz digraph, successes in green and failures in red
+  pub fn to_dot(&self) -> String {
+    let mut out = "digraph trace {\n  node [shape=box, fontname=monospace];\n".to_owned();
+    let mut parents: Vec<usize> = vec![];
+    for (id, (node, depth)) in self.with_depth().enumerate() {
+      let color = if node.is_success() {
+        "darkgreen"
+      } else {
+        "red"
+      };
+      let label = format!("{:?}", node.summary(&self.text));
+      out += &format!("  n{id} [label={label}, color={color}];\n");
+      parents.truncate(depth);
+      if let Some(parent) = parents.last() {
+        out += &format!("  n{parent} -> n{id};\n");
+      }
+      parents.push(id);
+    }
+    out + "}\n"
+  }
+
+  // a standalone page of nested, foldable parsers
+  pub fn to_html(&self) -> String {
+    fn node_html(node: &TraceNode, text: &str, out: &mut String) {
+      let class = if node.is_success() { "ok" } else { "failed" };
+      let summary = escape_html(&node.summary(text));
+      *out += &format!("<details open><summary class=\"{class}\">{summary}</summary>");
+      for child in &node.children {
+        node_html(child, text, out);
+      }
+      *out += "</details>\n";
+    }
+
+    let mut out = "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><style>\n\
+      body { font-family: monospace; }\n\
+      details { margin-left: 1.5em; border-left: 1px solid #ccc; padding-left: 0.5em; }\n\
+      .ok { color: darkgreen; }\n\
+      .failed { color: firebrick; }\n\
+      </style></head><body>\n"
+      .to_owned();
+    out += &format!("<pre>{}</pre>\n", escape_html(&self.text));
+    for root in &self.roots {
+      node_html(root, &self.text, &mut out);
+    }
+    out + "</body></html>\n"
+  }
+
+  fn with_depth(&self) -> impl Iterator<Item = (&TraceNode, usize)> {
+    let mut stack: Vec<_> = self.roots.iter().rev().map(|node| (node, 0)).collect();
+    std::iter::from_fn(move || {
+      let (node, depth) = stack.pop()?;
+      stack.extend(node.children.iter().rev().map(|child| (child, depth + 1)));
+      Some((node, depth))
+    })
+  }
+}
+
+impl fmt::Display for Trace {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.to_text())
+  }
+}
+
+fn escape_html(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+// Records the named parsers of a parse as they are entered and left, shared
+// by every `Ctx` of that parse. Like the memo table it is not part of the
+// `Ctx` identity.
+#[derive(Default)]
+pub(crate) struct Tracer {
+  // the parsers entered and not left yet, innermost last
+  running: RefCell<Vec<TraceNode>>,
+  roots: RefCell<Vec<TraceNode>>,
+}
+
+impl Tracer {
+  pub(crate) fn enter(&self, name: &str, start: usize) {
+    self.running.borrow_mut().push(TraceNode {
+      name: name.to_owned(),
+      start,
+      outcome: Outcome::Success(Span::new(start, start)),
+      children: vec![],
+    });
+  }
+
+  pub(crate) fn exit(&self, outcome: Outcome) {
+    let mut running = self.running.borrow_mut();
+    let mut node = running.pop().expect("exit without enter");
+    node.outcome = outcome;
+    match running.last_mut() {
+      Some(parent) => parent.children.push(node),
+      None => self.roots.borrow_mut().push(node),
+    }
+  }
+
+  // the parsers left so far
+  pub(crate) fn trace(&self, text: Rc<str>) -> Trace {
+    Trace {
+      text,
+      roots: self.roots.borrow().clone(),
+    }
+  }
+}
+
+impl fmt::Debug for Tracer {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Tracer({} roots)", self.roots.borrow().len())
+  }
+}
+
+impl PartialEq for Tracer {
+  fn eq(&self, _other: &Tracer) -> bool {
+    true
+  }
+}
+
+impl Eq for Tracer {}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn sample() -> Trace {
+    let tracer = Tracer::default();
+    tracer.enter("call", 0);
+    tracer.enter("ident", 0);
+    tracer.exit(Outcome::Success(Span::new(0, 1)));
+    tracer.enter("args", 2);
+    tracer.exit(Outcome::Failure {
+      index: 2,
+      expected: vec![")".to_owned(), "expr".to_owned()],
+      committed: true,
+    });
+    tracer.exit(Outcome::Failure {
+      index: 2,
+      expected: vec![")".to_owned()],
+      committed: true,
+    });
+    tracer.trace(Rc::from("f(<"))
+  }
+
+  #[test]
+  fn test_tree() {
+    let trace = sample();
+    assert_eq!(trace.roots().len(), 1);
+    let names: Vec<_> = trace.walk().map(|node| node.name.as_str()).collect();
+    assert_eq!(names, ["call", "ident", "args"]);
+    assert!(trace.roots()[0].children[0].is_success());
+  }
+
+  #[test]
+  fn test_render() {
+    let trace = sample();
+    assert_eq!(
+      trace.to_text(),
+      "call 0..2 cut, expected )\n  ident 0..1 ok \"f\"\n  args 2..2 cut, expected ), expr\n"
+    );
+
+    let dot = trace.to_dot();
+    assert!(dot.starts_with("digraph trace {"));
+    assert!(dot.contains("n1 [label=\"ident 0..1 ok \\\"f\\\"\", color=darkgreen];"));
+    assert!(dot.contains("n0 -> n1;"));
+    assert!(dot.contains("n0 -> n2;"));
+
+    let html = trace.to_html();
+    assert!(html.contains("<pre>f(&lt;</pre>"));
+    assert_eq!(html.matches("<details").count(), 3);
+    assert!(html.contains("<summary class=\"failed\">call 0..2 cut, expected )</summary>"));
+  }
+}